@@ -6,7 +6,8 @@ use libc::c_char;
 use std::ffi::{CStr, CString};
 use std::ptr::null_mut;
 use chessica::board::Board;
-use chessica_engine::search::{Search, TranspositionTable};
+use chessica::Move;
+use chessica_engine::search::{Score, Search, TranspositionTable};
 
 #[no_mangle]
 pub extern fn get_best_move(
@@ -16,35 +17,64 @@ pub extern fn get_best_move(
     tt_key_bits: u8,
     rng_seed: u64
 ) -> *mut c_char {
+    match search(initial_fen, uci_moves, max_depth, tt_key_bits, rng_seed) {
+        Some((best_move, _)) => {
+            let result = best_move.to_uci_string();
+            let c_str_result  = CString::new(result).unwrap();
+            c_str_result.into_raw()
+        },
+        None => null_mut()
+    }
+}
+
+// the best move followed by its score as in UCI, e.g. "g1f3 cp 35" or "d8h4 mate 1"
+#[no_mangle]
+pub extern "C" fn get_best_move_with_score(
+    initial_fen: *const c_char,
+    uci_moves: *const c_char,
+    max_depth: u8,
+    tt_key_bits: u8,
+    rng_seed: u64
+) -> *mut c_char {
+    match search(initial_fen, uci_moves, max_depth, tt_key_bits, rng_seed) {
+        Some((best_move, score)) => {
+            let result = format!("{} {}", best_move.to_uci_string(), score.to_uci_string());
+            let c_str_result  = CString::new(result).unwrap();
+            c_str_result.into_raw()
+        },
+        None => null_mut()
+    }
+}
+
+fn search(
+    initial_fen: *const c_char,
+    uci_moves: *const c_char,
+    max_depth: u8,
+    tt_key_bits: u8,
+    rng_seed: u64
+) -> Option<(Move, Score)> {
     if initial_fen.is_null() || uci_moves.is_null() {
-        return null_mut();
+        return None;
     }
     let initial_fen = unsafe {
         CStr::from_ptr(initial_fen)
     };
-    let initial_fen_str = initial_fen.to_str().unwrap();
+    let initial_fen_str = initial_fen.to_str().ok()?;
     let uci_moves = unsafe {
         CStr::from_ptr(uci_moves)
     };
-    let uci_moves_str = uci_moves.to_str().unwrap();
-    let mut board = Board::parse_fen(initial_fen_str).unwrap();
-    if uci_moves_str.len() > 0 {
+    let uci_moves_str = uci_moves.to_str().ok()?;
+    let mut board = Board::parse_fen_strict(initial_fen_str).ok()?;
+    if !uci_moves_str.is_empty() {
         for uci_move in uci_moves_str.split(",") {
-            if let Err(_) = board.push_uci(uci_move) {
-                return null_mut();
-            }
+            board.push_uci(uci_move).ok()?;
         }
     }
-    let mut tt = TranspositionTable::new(tt_key_bits);
+    // the table is sized in MB, keep the room of 2^tt_key_bits entries of 8 bytes
+    let tt = TranspositionTable::new((8usize << tt_key_bits.min(40)) >> 20);
     let mut search = Search::new_with_rng(max_depth as usize, rng_seed);
-    match search.search(&board, &mut tt) {
-        Some(best_move) => {
-            let result = best_move.to_uci_string();
-            let c_str_result  = CString::new(result).unwrap();
-            c_str_result.into_raw()
-        },
-        None => null_mut()
-    }
+    let best_move = search.search(&board, &tt)?;
+    Some((best_move, search.get_score()))
 }
 
 #[no_mangle]
@@ -73,4 +103,24 @@ mod tests {
         }.to_str().unwrap();
         assert_eq!(best_move_str, "g1f3");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_get_best_move_invalid_fen() {
+        let initial_fen_cstr = CString::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1").unwrap();
+        let uci_moves_cstr = CString::new("").unwrap();
+        let best_move_ptr = get_best_move(initial_fen_cstr.as_ptr(), uci_moves_cstr.as_ptr(), 5, 20, 0);
+        assert!(best_move_ptr.is_null());
+    }
+
+    #[test]
+    fn test_get_best_move_with_score() {
+        let initial_fen_cstr = CString::new("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1").unwrap();
+        let uci_moves_cstr = CString::new("").unwrap();
+        let result_ptr = get_best_move_with_score(initial_fen_cstr.as_ptr(), uci_moves_cstr.as_ptr(), 3, 20, 0);
+        let result = unsafe {
+            CStr::from_ptr(result_ptr)
+        }.to_str().unwrap().to_string();
+        free_string(result_ptr);
+        assert_eq!(result, "g1g8 mate 1");
+    }
+}
@@ -0,0 +1,85 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::process::exit;
+
+use chessica::book::PolyglotBookBuilder;
+use chessica::pgn::PgnReader;
+
+const USAGE: &str = "Usage: book [-max-ply <n>] [-min-games <n>] [-min-score <percent>] <output.bin> <pgn_file>...";
+
+pub fn build_book(args: &[String]) {
+    let mut max_ply: usize = 30;
+    let mut min_games: u32 = 1;
+    let mut min_score: f64 = 0.0;
+    let mut files: Vec<&String> = vec![];
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-max-ply" | "-min-games" | "-min-score" => {
+                let value = match args.get(i + 1) {
+                    Some(value) => value,
+                    None => {
+                        println!("{}", USAGE);
+                        exit(-1);
+                    }
+                };
+                let parsed = match args[i].as_str() {
+                    "-max-ply" => value.parse().map(|v| max_ply = v).is_ok(),
+                    "-min-games" => value.parse().map(|v| min_games = v).is_ok(),
+                    _ => value.parse::<f64>().map(|v| min_score = v / 100.0).is_ok(),
+                };
+                if !parsed {
+                    println!("Error: invalid value for {}: {}", args[i], value);
+                    exit(-1);
+                }
+                i += 2;
+            }
+            _ => {
+                files.push(&args[i]);
+                i += 1;
+            }
+        }
+    }
+    if files.len() < 2 {
+        println!("{}", USAGE);
+        exit(-1);
+    }
+
+    let mut builder = PolyglotBookBuilder::new(max_ply, min_games, min_score);
+    let mut skipped = 0;
+    for &path in files[1..].iter() {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                println!("Error: cannot open {}: {}", path, e);
+                exit(-1);
+            }
+        };
+        for game in PgnReader::new(BufReader::new(file)) {
+            match game {
+                Ok(game) => {
+                    if !builder.add_game(&game.starting_position, &game.moves, &game.result) {
+                        skipped += 1;
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Skipping game in {}: {:?}", path, e);
+                    skipped += 1;
+                }
+            }
+        }
+    }
+
+    let book = builder.build();
+    if let Err(e) = book.save(files[0]) {
+        println!("Error: cannot write {}: {}", files[0], e);
+        exit(-1);
+    }
+    println!(
+        "Wrote {} entries from {} games to {} ({} games skipped)",
+        book.len(),
+        builder.games(),
+        files[0],
+        skipped
+    );
+}
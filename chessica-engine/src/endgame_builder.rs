@@ -0,0 +1,64 @@
+use std::path::Path;
+use std::process::exit;
+use std::time::Instant;
+
+use chessica::endgame::EndgameTables;
+
+const USAGE: &str = "Usage: endgame [-verify] <dir> <material>...";
+
+pub fn build_endgame_tables(args: &[String]) {
+    let verify = args.first().is_some_and(|arg| arg == "-verify");
+    let args = if verify { &args[1..] } else { args };
+    if args.len() < 2 {
+        println!("{}", USAGE);
+        exit(-1);
+    }
+    let dir = Path::new(&args[0]);
+
+    // reuse the tables that were already generated
+    let mut tables = if dir.is_dir() {
+        match EndgameTables::open_dir(dir) {
+            Ok(tables) => tables,
+            Err(e) => {
+                println!("Error: cannot open tables in {}: {}", dir.display(), e);
+                exit(-1);
+            }
+        }
+    } else {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            println!("Error: cannot create {}: {}", dir.display(), e);
+            exit(-1);
+        }
+        EndgameTables::new()
+    };
+
+    for material in args[1..].iter() {
+        let start = Instant::now();
+        let generated = match tables.generate(material) {
+            Ok(generated) => generated,
+            Err(e) => {
+                println!("Error: cannot generate {}: {}", material, e);
+                exit(-1);
+            }
+        };
+        println!(
+            "Generated {} tables for {} ({:.3} sec)",
+            generated.len(),
+            material,
+            start.elapsed().as_secs_f32()
+        );
+        if verify {
+            if let Err(e) = tables.verify(material) {
+                println!("Error: {}", e);
+                exit(-1);
+            }
+            println!("Verified {}", material);
+        }
+    }
+
+    if let Err(e) = tables.save_dir(dir) {
+        println!("Error: cannot write tables to {}: {}", dir.display(), e);
+        exit(-1);
+    }
+    println!("Wrote tables {} to {}", tables.names().join(", "), dir.display());
+}
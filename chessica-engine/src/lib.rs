@@ -1,2 +1,3 @@
 pub mod search;
+pub mod time_manager;
 pub mod uci;
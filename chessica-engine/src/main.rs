@@ -1,3 +1,5 @@
+mod book_builder;
+mod endgame_builder;
 mod selfplay;
 
 extern crate chessica;
@@ -23,6 +25,12 @@ fn main() {
                 "selfplay" => {
                     selfplay::selfplay();
                 },
+                "book" => {
+                    book_builder::build_book(&args[2..]);
+                },
+                "endgame" => {
+                    endgame_builder::build_endgame_tables(&args[2..]);
+                },
                 "bmagics" => {
                     let start = Instant::now();
                     let bishop_magics = find_fancy_bishop_magics(5, 1_000_000);
@@ -61,7 +69,13 @@ fn main() {
                             exit(-1);
                         }
                         let fen = args.get(4).unwrap();
-                        let mut board = Board::parse_fen(fen).expect("Invalid fen");
+                        let mut board = match Board::parse_fen(fen) {
+                            Ok(board) => board,
+                            Err(e) => {
+                                println!("Error: invalid fen: {}", e);
+                                exit(-1);
+                            }
+                        };
                         // ensure magic bitboards are initialised
                         perft(&mut board, 1);
                         let mut hash_table = vec![PerftHashEntry(0, 0); 1 << hash_bits];
@@ -79,7 +93,13 @@ fn main() {
                         }
                     } else {
                         let fen = arg3;
-                        let mut board = Board::parse_fen(fen).expect("Invalid fen");
+                        let mut board = match Board::parse_fen(fen) {
+                            Ok(board) => board,
+                            Err(e) => {
+                                println!("Error: invalid fen: {}", e);
+                                exit(-1);
+                            }
+                        };
                         // ensure magic bitboards are initialised
                         perft(&mut board, 1);
                         for i in 0..max_depth {
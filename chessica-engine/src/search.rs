@@ -1,10 +1,102 @@
 use std::ops;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::time::Duration;
 use itertools::Itertools;
 use rand::prelude::StdRng;
 use rand::{Rng, SeedableRng};
 use chessica::board::Board;
-use chessica::Move;
+use chessica::endgame::{Dtm, EndgameTables};
+use chessica::syzygy::{Tablebase, Wdl};
+use chessica::{Move, PackedMove, Piece, Side};
 use crate::search::Score::{LowerBound, UpperBound, Exact};
+use crate::time_manager::{SearchLimits, TimeManager};
+
+// the deepest search when it is limited by time, nodes or nothing
+pub const MAX_DEPTH: usize = 64;
+// the main thread checks the limits of the search every this many nodes
+const LIMITS_CHECK_NODES: u64 = 1024;
+// being checkmated at the root; mates further away score closer to zero by one per ply
+pub const MATE_SCORE: i16 = 30_000;
+// the longest mate or tablebase win that scores are adjusted for
+const MAX_MATE_PLY: i16 = 256;
+// tablebase wins score below any mate found by the search
+const TB_WIN_SCORE: i16 = 20_000;
+// null move pruning is not used near scores that mean a win or a loss
+const MAX_EVAL_SCORE: i16 = 10_000;
+// history scores saturate at this value
+const HISTORY_MAX: i32 = 16_384;
+// Lazy SMP: helper thread i leaves out the iterations whose (depth + SKIP_PHASE[i]) / SKIP_SIZE[i]
+// is odd, so that the helpers run ahead of the main thread and at different depths from each other
+const SKIP_SIZE: [usize; 20] = [1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4];
+const SKIP_PHASE: [usize; 20] = [0, 1, 0, 1, 2, 3, 0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 5, 6, 7];
+// the helpers add up to this much to the history score of each quiet move, more than most
+// history scores early in the search
+const HELPER_JITTER: i32 = 512;
+// the main thread reports the root move it is searching once the search has taken this long
+const CURRMOVE_MIN_TIME: Duration = Duration::from_secs(3);
+// null move fail highs at this depth or deeper are verified by a reduced search without null moves
+const NULL_MOVE_VERIFICATION_DEPTH: usize = 4;
+
+// Parameters of the selective search: depths are in plies and margins in centipawns per ply
+#[derive(Debug, Clone)]
+pub struct SearchParams {
+    // late move reductions of quiet moves, by base + ln(depth) * ln(move number) / divisor
+    pub lmr_min_depth: usize,
+    pub lmr_min_moves: usize,
+    pub lmr_base: f64,
+    pub lmr_divisor: f64,
+    // reverse futility pruning: fail high if the static eval is that far above beta
+    pub rfp_max_depth: usize,
+    pub rfp_margin: i16,
+    // futility pruning: skip quiet moves if the static eval is that far below alpha
+    pub futility_max_depth: usize,
+    pub futility_margin: i16,
+    // razoring: drop into qsearch if the static eval is that far below alpha
+    pub razoring_max_depth: usize,
+    pub razoring_margin: i16,
+    // late move pruning: skip quiet moves after base + depth^2 of them
+    pub lmp_max_depth: usize,
+    pub lmp_base: usize,
+    // aspiration windows: the root window around the previous score, doubled on each fail; 0
+    // searches with a full window
+    pub aspiration_min_depth: usize,
+    pub aspiration_window: i16,
+    // singular extensions: extend the hash move if all other moves fail low against its TT score
+    // minus the margin, in a search of half the depth
+    pub singular_min_depth: usize,
+    pub singular_margin: i16,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        SearchParams {
+            lmr_min_depth: 3,
+            lmr_min_moves: 3,
+            lmr_base: 0.75,
+            lmr_divisor: 2.25,
+            rfp_max_depth: 6,
+            rfp_margin: 80,
+            futility_max_depth: 3,
+            futility_margin: 120,
+            razoring_max_depth: 2,
+            razoring_margin: 300,
+            lmp_max_depth: 4,
+            lmp_base: 3,
+            aspiration_min_depth: 4,
+            aspiration_window: 50,
+            singular_min_depth: 6,
+            singular_margin: 20,
+        }
+    }
+}
+
+impl SearchParams {
+    fn lmr_reduction(&self, depth: usize, move_number: usize) -> usize {
+        let reduction = self.lmr_base + (depth as f64).ln() * (move_number as f64).ln() / self.lmr_divisor;
+        reduction.max(0.0) as usize
+    }
+}
 
 #[derive(Debug, Copy, Clone)]
 pub enum Score {
@@ -13,6 +105,61 @@ pub enum Score {
     Exact(i16)
 }
 
+impl Score {
+    fn value(self) -> i16 {
+        match self {
+            LowerBound(s) | UpperBound(s) | Exact(s) => s
+        }
+    }
+
+    // the number of moves to mate, negative if the side to move is getting mated
+    pub fn mate_in(self) -> Option<i16> {
+        let value = self.value();
+        if value >= MATE_SCORE - MAX_MATE_PLY {
+            Some((MATE_SCORE - value + 1) / 2)
+        } else if value <= -MATE_SCORE + MAX_MATE_PLY {
+            Some(-(MATE_SCORE + value) / 2)
+        } else {
+            None
+        }
+    }
+
+    // e.g. "cp 35", "mate 3" or "cp -20 upperbound"
+    pub fn to_uci_string(self) -> String {
+        let score = match self.mate_in() {
+            Some(moves) => format!("mate {}", moves),
+            None => format!("cp {}", self.value()),
+        };
+        match self {
+            LowerBound(_) => format!("{} lowerbound", score),
+            UpperBound(_) => format!("{} upperbound", score),
+            Exact(_) => score,
+        }
+    }
+}
+
+// Mate and tablebase scores count plies from the root; the TT stores them counting from the
+// position itself, so that they stay correct when the position is reached at another ply
+fn score_to_tt(score: i16, ply: usize) -> i16 {
+    if score >= TB_WIN_SCORE - MAX_MATE_PLY {
+        score + ply as i16
+    } else if score <= -TB_WIN_SCORE + MAX_MATE_PLY {
+        score - ply as i16
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i16, ply: usize) -> i16 {
+    if score >= TB_WIN_SCORE - MAX_MATE_PLY {
+        score - ply as i16
+    } else if score <= -TB_WIN_SCORE + MAX_MATE_PLY {
+        score + ply as i16
+    } else {
+        score
+    }
+}
+
 impl ops::Neg for Score {
     type Output = Score;
 
@@ -30,105 +177,285 @@ enum TTScore {
     Alpha(i16),
     Beta(i16, Move),
     Pv(i16, Move),
-    None
 }
 
-#[derive(Debug, Copy, Clone)]
-struct TTEntry {
-    position_hash: u64,
+const BOUND_NONE: u8 = 0;
+const BOUND_UPPER: u8 = 1;
+const BOUND_LOWER: u8 = 2;
+const BOUND_EXACT: u8 = 3;
+const GENERATIONS: u8 = 64;
+
+// the best move (a1a1 if none), the score, the depth, and the bound in the low 2 bits with the
+// generation of the search above them, packed into 48 bits
+#[derive(Debug, Copy, Clone, Default)]
+struct TTData {
+    move_: PackedMove,
+    score: i16,
     depth: u8,
-    score: TTScore,
+    bound_generation: u8,
 }
 
-impl TTEntry {
-    fn empty() -> Self {
-        TTEntry {
-            position_hash: 0,
-            depth: 0,
-            score: TTScore::None
+impl TTData {
+    fn from_bits(bits: u64) -> Self {
+        TTData {
+            move_: PackedMove(bits as u16),
+            score: (bits >> 16) as u16 as i16,
+            depth: (bits >> 32) as u8,
+            bound_generation: (bits >> 40) as u8,
         }
     }
+
+    fn to_bits(self) -> u64 {
+        self.move_.0 as u64 | (self.score as u16 as u64) << 16 | (self.depth as u64) << 32 | (self.bound_generation as u64) << 40
+    }
+
+    fn bound(&self) -> u8 {
+        self.bound_generation & 3
+    }
+
+    fn generation(&self) -> u8 {
+        self.bound_generation >> 2
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bound() == BOUND_NONE
+    }
+
+    // how many searches ago the entry was written
+    fn age(&self, generation: u8) -> u8 {
+        generation.wrapping_sub(self.generation()) % GENERATIONS
+    }
+}
+
+// 16 bytes: the data, and the position hash XORed with it, so that an entry torn by threads
+// writing it at the same time no longer matches the position and is ignored
+#[derive(Debug, Default)]
+struct TTEntry {
+    key_xor_data: AtomicU64,
+    data: AtomicU64,
+}
+
+impl TTEntry {
+    fn load(&self) -> (u64, TTData) {
+        let data = self.data.load(Ordering::Relaxed);
+        (self.key_xor_data.load(Ordering::Relaxed) ^ data, TTData::from_bits(data))
+    }
+
+    fn store(&self, key: u64, data: TTData) {
+        let data = data.to_bits();
+        self.key_xor_data.store(key ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+const BUCKET_SIZE: usize = 4;
+
+#[derive(Debug, Default)]
+struct TTBucket {
+    entries: [TTEntry; BUCKET_SIZE],
 }
 
+// shared by the threads of a search without locking
 pub struct TranspositionTable {
-    size: usize,
-    entries: Vec<TTEntry>
+    buckets: Vec<TTBucket>,
+    generation: AtomicU8,
 }
 
 impl TranspositionTable {
-    pub fn new(key_bits: u8) -> TranspositionTable {
-        let size: usize = 43 + (1 << key_bits);
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let bucket_count = (size_mb << 20) / std::mem::size_of::<TTBucket>();
         TranspositionTable {
-            size,
-            entries: vec![TTEntry::empty(); size as usize]
+            buckets: (0..bucket_count.max(1)).map(|_| TTBucket::default()).collect(),
+            generation: AtomicU8::new(0),
         }
     }
 
-    fn put(&mut self, position: &Board, depth: u8, score: TTScore) {
-        let idx = position.hash() as usize % self.size;
-        let entry = &mut self.entries[idx];
-        if entry.position_hash == position.hash() && depth < entry.depth {
+    fn bucket(&self, key: u64) -> &TTBucket {
+        &self.buckets[key as usize % self.buckets.len()]
+    }
+
+    fn entry(&self, position: &Board) -> Option<TTData> {
+        let key = position.hash();
+        self.bucket(key).entries.iter()
+            .map(TTEntry::load)
+            .find(|(entry_key, data)| !data.is_empty() && *entry_key == key)
+            .map(|(_, data)| data)
+    }
+
+    // entries of earlier searches are replaced first, then the shallowest ones
+    fn put(&self, position: &Board, depth: u8, ply: usize, score: TTScore) {
+        let key = position.hash();
+        let generation = self.generation.load(Ordering::Relaxed);
+        let bucket = self.bucket(key);
+        let entries = bucket.entries.each_ref().map(TTEntry::load);
+        let idx = entries.iter().position(|(entry_key, data)| !data.is_empty() && *entry_key == key)
+            .unwrap_or_else(|| {
+                (0..BUCKET_SIZE)
+                    .min_by_key(|&i| entries[i].1.depth as i32 - 8 * entries[i].1.age(generation) as i32)
+                    .unwrap()
+            });
+        let (entry_key, entry) = entries[idx];
+        let (bound, score, move_) = match score {
+            TTScore::Alpha(score) => (BOUND_UPPER, score, None),
+            TTScore::Beta(score, move_) => (BOUND_LOWER, score, Some(move_)),
+            TTScore::Pv(score, move_) => (BOUND_EXACT, score, Some(move_)),
+        };
+        let is_same_position = !entry.is_empty() && entry_key == key;
+        if is_same_position && depth < entry.depth && entry.age(generation) == 0 && bound != BOUND_EXACT {
             return;
         }
-        entry.position_hash = position.hash();
-        entry.depth = depth;
-        entry.score = score;
+        // an upper bound has no best move, so keep the one found before
+        let move_ = match move_ {
+            Some(move_) => PackedMove::pack(&move_),
+            None if is_same_position => entry.move_,
+            None => PackedMove::default(),
+        };
+        bucket.entries[idx].store(key, TTData {
+            move_,
+            score: score_to_tt(score, ply),
+            depth,
+            bound_generation: bound | generation << 2,
+        });
     }
 
-    fn get(&self, position: &Board, depth: u8, alpha: i16, beta: i16) -> Option<Score> {
-        let idx = position.hash() as usize % self.size;
-        let entry = &self.entries[idx];
-        if entry.depth >= depth && entry.position_hash == position.hash() {
-            return match entry.score {
-                TTScore::Pv(score, _) => Some(Exact(score)),
-                TTScore::Alpha(score) if score <= alpha => Some(UpperBound(score)),
-                TTScore::Beta(score, _) if score >= beta => Some(LowerBound(score)),
-                _ => None
-            }
+    fn get(&self, position: &Board, depth: u8, ply: usize, alpha: i16, beta: i16) -> Option<Score> {
+        let entry = self.entry(position)?;
+        if entry.depth < depth {
+            return None;
         }
-        None
+        let score = score_from_tt(entry.score, ply);
+        match entry.bound() {
+            BOUND_EXACT => Some(Exact(score)),
+            BOUND_UPPER if score <= alpha => Some(UpperBound(score)),
+            BOUND_LOWER if score >= beta => Some(LowerBound(score)),
+            _ => None
+        }
+    }
+
+    // the move of an entry that is not an upper bound, with its score and depth
+    fn get_move_with_score(&self, position: &Board, ply: usize, moves: &[Move]) -> Option<(Move, i16, u8)> {
+        let entry = self.entry(position)?;
+        if entry.bound() == BOUND_UPPER {
+            return None;
+        }
+        let move_ = *moves.iter().find(|m| PackedMove::pack(m) == entry.move_)?;
+        Some((move_, score_from_tt(entry.score, ply), entry.depth))
     }
 
     fn get_pv_move(&self, position: &Board) -> Option<Move> {
-        let idx = position.hash() as usize % self.size;
-        let entry = &self.entries[idx];
-        if entry.position_hash == position.hash() {
-            return match entry.score {
-                TTScore::Pv(_, move_) => Some(move_),
-                _ => None
-            }
+        let entry = self.entry(position)?;
+        if entry.bound() != BOUND_EXACT {
+            return None;
         }
-        None
+        position.legal_moves().into_iter().find(|m| PackedMove::pack(m) == entry.move_)
     }
 
-    fn get_move(&self, position: &Board) -> Option<Move> {
-        let idx = position.hash() as usize % self.size;
-        let entry = &self.entries[idx];
-        if entry.position_hash == position.hash() {
-            return match entry.score {
-                TTScore::Pv(_, move_) => Some(move_),
-                TTScore::Beta(_, move_) => Some(move_),
-                _ => None
-            }
+    // the best move found before among the given legal moves of the position
+    fn get_move(&self, position: &Board, moves: &[Move]) -> Option<Move> {
+        let entry = self.entry(position)?;
+        if entry.move_ == PackedMove::default() {
+            return None;
         }
-        None
+        moves.iter().find(|m| PackedMove::pack(m) == entry.move_).copied()
     }
 
-    pub fn clear(&mut self) {
-        self.entries.fill(TTEntry::empty());
+    // entries of earlier searches are replaced first
+    pub fn new_search(&self) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        self.generation.store((generation + 1) % GENERATIONS, Ordering::Relaxed);
     }
+
+    // permille of the sampled entries used by the current search
+    pub fn hashfull(&self) -> u32 {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let sample = self.buckets.iter().take(1000 / BUCKET_SIZE).flat_map(|bucket| bucket.entries.iter());
+        let (used, total) = sample.fold((0, 0), |(used, total), entry| {
+            let (_, data) = entry.load();
+            (used + (!data.is_empty() && data.age(generation) == 0) as u32, total + 1)
+        });
+        used * 1000 / total
+    }
+
+    pub fn clear(&self) {
+        for entry in self.buckets.iter().flat_map(|bucket| bucket.entries.iter()) {
+            entry.store(0, TTData::default());
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+}
+
+// The progress of the main thread: reported after every iteration, also when it fails high or
+// low in the aspiration window, and for every root move of a long search
+#[derive(Debug, Clone)]
+pub enum SearchInfo {
+    Iteration {
+        depth: usize,
+        // the deepest ply reached, including extensions and the quiescence search
+        seldepth: usize,
+        score: Score,
+        nodes: u64,
+        time: Duration,
+        hashfull: u32,
+        // positions found in the tablebase or endgame tables
+        tbhits: u32,
+        pv: Vec<Move>,
+    },
+    CurrentMove {
+        depth: usize,
+        move_: Move,
+        // counting from 1
+        move_number: usize,
+    },
 }
 
+type InfoCallback = Box<dyn FnMut(&SearchInfo) + Send>;
+
 pub struct Search {
     max_depth: usize,
+    // extensions stop at this many plies from the root
+    max_ply: usize,
     eval_count: u32,
     cutoff_count: u32,
     q_cutoff_count: u32,
     tt_hit_count: u32,
+    tb_hits: u32,
+    tablebase: Option<Arc<Tablebase>>,
+    endgame_tables: Option<Arc<EndgameTables>>,
+    root_moves: Option<Vec<Move>>,
+    null_move_min_ply: usize,
+    null_cutoff_count: u32,
+    params: SearchParams,
+    // quiet move ordering: two killer moves per ply, history scores by side, from and to square,
+    // and countermoves by the piece and destination of the previous move
+    killers: Vec<[Option<Move>; 2]>,
+    history: Vec<i32>,
+    countermoves: Vec<Option<Move>>,
+    // the move played at each ply of the current line, None for a null move
+    ply_moves: Vec<Option<Move>>,
+    last_score: Score,
     last_pv: Vec<Move>,
+    // the depth of the last completed iteration
+    last_depth: usize,
+    seldepth: usize,
     pv_table: Vec<Vec<Move>>,
     rng_seed: u64,
-    rng: StdRng
+    rng: StdRng,
+    // Lazy SMP: the helper threads search the same position over the shared TT, each ordering quiet
+    // moves a little differently, until the main thread is done
+    threads: usize,
+    helper_id: usize,
+    stop: Arc<AtomicBool>,
+    // the main thread stops the search at the limits, though not while pondering
+    limits: SearchLimits,
+    time_manager: TimeManager,
+    pondering: Arc<AtomicBool>,
+    aborted: bool,
+    // the nodes of this thread, and those of all threads counted so far
+    nodes: u64,
+    total_nodes: Arc<AtomicU64>,
+    // the nodes spent on each root move in the current iteration
+    root_move_nodes: Vec<(Move, u64)>,
+    info_callback: Option<InfoCallback>
 }
 
 impl Search {
@@ -137,21 +464,121 @@ impl Search {
     }
 
     pub fn new_with_rng(max_depth: usize, rng_seed: u64) -> Self {
+        let max_ply = 2 * max_depth;
         let mut pv_table = vec![];
-        for i in 0..max_depth {
-            pv_table.push(Vec::with_capacity(max_depth - i));
+        for i in 0..max_ply {
+            pv_table.push(Vec::with_capacity(max_ply - i));
         }
         Search {
             max_depth,
+            max_ply,
             eval_count: 0,
             cutoff_count: 0,
             q_cutoff_count: 0,
             tt_hit_count: 0,
+            tb_hits: 0,
+            tablebase: None,
+            endgame_tables: None,
+            root_moves: None,
+            null_move_min_ply: 0,
+            null_cutoff_count: 0,
+            params: SearchParams::default(),
+            killers: vec![[None; 2]; max_ply + 1],
+            history: vec![0; 2 * 64 * 64],
+            countermoves: vec![None; 7 * 64],
+            ply_moves: vec![None; max_ply + 1],
+            last_score: Exact(0),
             last_pv: vec![],
+            last_depth: 0,
+            seldepth: 0,
             pv_table,
             rng_seed,
-            rng: StdRng::seed_from_u64(rng_seed)
+            rng: StdRng::seed_from_u64(rng_seed),
+            threads: 1,
+            helper_id: 0,
+            stop: Arc::new(AtomicBool::new(false)),
+            limits: SearchLimits::default(),
+            time_manager: TimeManager::new(&SearchLimits::default(), Side::White),
+            pondering: Arc::new(AtomicBool::new(false)),
+            aborted: false,
+            nodes: 0,
+            total_nodes: Arc::new(AtomicU64::new(0)),
+            root_move_nodes: vec![],
+            info_callback: None
+        }
+    }
+
+    pub fn set_params(&mut self, params: SearchParams) {
+        self.params = params;
+    }
+
+    pub fn set_tablebase(&mut self, tablebase: Arc<Tablebase>) {
+        self.tablebase = Some(tablebase);
+    }
+
+    pub fn set_endgame_tables(&mut self, endgame_tables: Arc<EndgameTables>) {
+        self.endgame_tables = Some(endgame_tables);
+    }
+
+    // the search returns the best move of its last completed iteration once the flag is set
+    pub fn set_stop_flag(&mut self, stop: Arc<AtomicBool>) {
+        self.stop = stop;
+    }
+
+    pub fn set_limits(&mut self, limits: SearchLimits) {
+        self.limits = limits;
+    }
+
+    // the time limits only apply once the flag is cleared
+    pub fn set_ponder_flag(&mut self, pondering: Arc<AtomicBool>) {
+        self.pondering = pondering;
+    }
+
+    // only the main thread reports its progress
+    pub fn set_info_callback(&mut self, info_callback: impl FnMut(&SearchInfo) + Send + 'static) {
+        self.info_callback = Some(Box::new(info_callback));
+    }
+
+    // the nodes searched by all threads
+    pub fn nodes(&self) -> u64 {
+        self.total_nodes.load(Ordering::Relaxed)
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    pub fn tb_hits(&self) -> u32 {
+        self.tb_hits
+    }
+
+    fn _probe_wdl(&mut self, board: &mut Board, pv_idx: usize) -> Option<Score> {
+        // the WDL tables ignore the fifty move rule counter, so only probe right after it was reset
+        if board.half_move_clock() != 0 {
+            return None;
         }
+        let wdl = self.tablebase.as_ref()?.probe_wdl(board)?;
+        self.tb_hits += 1;
+        let score = match wdl {
+            Wdl::Win => TB_WIN_SCORE - pv_idx as i16,
+            Wdl::CursedWin => 1,
+            Wdl::Draw => 0,
+            Wdl::BlessedLoss => -1,
+            Wdl::Loss => -TB_WIN_SCORE + pv_idx as i16,
+        };
+        Some(Exact(score))
+    }
+
+    fn _probe_dtm(&mut self, board: &Board, pv_idx: usize) -> Option<Score> {
+        let dtm = self.endgame_tables.as_ref()?.probe(board)?;
+        self.tb_hits += 1;
+        // scored like a mate found by the search, at its distance from the root
+        let score = match dtm {
+            Dtm::Win(plies) => MATE_SCORE - (pv_idx + plies as usize) as i16,
+            Dtm::Draw => 0,
+            Dtm::Loss(plies) => -MATE_SCORE + (pv_idx + plies as usize) as i16,
+        };
+        Some(Exact(score))
     }
 
     fn _eval(&mut self, board: &Board) -> i16 {
@@ -159,14 +586,9 @@ impl Search {
 
         // TODO: work out how to use traits (?) to make eval function pluggable
 
-        if board.is_in_check() && board.legal_moves().is_empty() {
-            // checkmate!
-            return -30_000;
-        }
-        else if board.is_draw_by_threefold_repetition() {
-            return 0;
-        }
-        else if board.is_draw_by_fifty_move_rule() {
+        // only the draw rules that need no move generation: mate and stalemate are found by the
+        // search when a position has no moves
+        if board.is_draw_by_threefold_repetition() || board.is_draw_by_fifty_move_rule() || board.is_insufficient_material() {
             return 0;
         }
 
@@ -179,7 +601,27 @@ impl Search {
         board.get_pst_negamax_score() + perturbation
     }
 
-    fn _qsearch(&mut self, board: &mut Board, alpha: i16, beta: i16) -> Score {
+    fn _is_stopped(&self) -> bool {
+        self.aborted || self.stop.load(Ordering::Relaxed)
+    }
+
+    fn _count_node(&mut self) {
+        self.nodes += 1;
+        if !self.nodes.is_multiple_of(LIMITS_CHECK_NODES) {
+            return;
+        }
+        let total_nodes = self.total_nodes.fetch_add(LIMITS_CHECK_NODES, Ordering::Relaxed) + LIMITS_CHECK_NODES;
+        if self.helper_id == 0 {
+            let is_out_of_time = !self.pondering.load(Ordering::Relaxed) && self.time_manager.is_hard_limit_reached();
+            if is_out_of_time || self.limits.nodes.is_some_and(|nodes| total_nodes >= nodes) {
+                self.aborted = true;
+            }
+        }
+    }
+
+    fn _qsearch(&mut self, board: &mut Board, ply: usize, alpha: i16, beta: i16) -> Score {
+        self._count_node();
+        self.seldepth = self.seldepth.max(ply);
         let in_check = board.is_in_check();
 
         let mut alpha = alpha;
@@ -204,7 +646,7 @@ impl Search {
                 // stalemate!
                 return Exact(0)
             }
-            return Exact(stand_pat_score);
+            return Exact(-MATE_SCORE + ply as i16);
         }
 
         if !in_check {
@@ -225,7 +667,7 @@ impl Search {
 
         for &move_ in moves.iter() {
             board.push(&move_);
-            let score = -self._qsearch(board, -beta, -alpha);
+            let score = -self._qsearch(board, ply + 1, -beta, -alpha);
             board.pop();
             match score {
                 LowerBound(score) => {
@@ -260,13 +702,42 @@ impl Search {
         }
     }
 
-    fn _search(&mut self, board: &mut Board, tt: &mut TranspositionTable, depth: usize, pv_idx: usize, alpha: i16, beta: i16) -> Score {
+    fn _search(&mut self, board: &mut Board, tt: &TranspositionTable, depth: usize, pv_idx: usize, alpha: i16, beta: i16) -> Score {
+        // the score of a stopped search is never used
+        if self._is_stopped() {
+            return Exact(0);
+        }
+        self._count_node();
+        self.seldepth = self.seldepth.max(pv_idx);
 
+        // check extension: no standing pat while in check
+        let depth = if depth == 0 && pv_idx < self.max_ply && board.is_in_check() { 1 } else { depth };
         if depth == 0 {
-            return self._qsearch(board, alpha, beta);
+            return self._qsearch(board, pv_idx, alpha, beta);
         }
 
-        if let Some(tt_score) = tt.get(board, depth as u8, alpha, beta) {
+        // mate distance pruning: neither getting mated here nor mating next move can change the
+        // outcome when a shorter mate is already known
+        if pv_idx > 0 {
+            let mated_score = -MATE_SCORE + pv_idx as i16;
+            if mated_score >= beta {
+                self.pv_table[pv_idx].truncate(0);
+                return LowerBound(mated_score);
+            }
+            let mating_score = MATE_SCORE - pv_idx as i16 - 1;
+            if mating_score <= alpha {
+                self.pv_table[pv_idx].truncate(0);
+                return UpperBound(mating_score);
+            }
+        }
+
+        // a root restricted by the tablebase must not return a move from the TT
+        let tt_score = if pv_idx == 0 && self.root_moves.is_some() {
+            None
+        } else {
+            tt.get(board, depth as u8, pv_idx, alpha, beta)
+        };
+        if let Some(tt_score) = tt_score {
             self.tt_hit_count += 1;
             self.pv_table[pv_idx].truncate(0);
             if let Some(pv_move) = tt.get_pv_move(board) {
@@ -275,50 +746,134 @@ impl Search {
             return tt_score;
         }
 
-        let mut moves = board.legal_moves();
-        if moves.is_empty() {
-            self.pv_table[pv_idx].truncate(0);
-            let score = if board.is_in_check() { -30_000 } else { 0 };
-            return Exact(score);
+        if pv_idx > 0 {
+            if let Some(tb_score) = self._probe_dtm(board, pv_idx).or_else(|| self._probe_wdl(board, pv_idx)) {
+                self.pv_table[pv_idx].truncate(0);
+                return tb_score;
+            }
         }
 
-        // move ordering: hash move first, then PV move from previous search, then captures in MVV/LVA order
-        // TODO: killer move heuristic?
-        let mut sort_from = 0;
-        if let Some(hash_move) = tt.get_move(board) {
-            if let Some(h) = moves.iter().skip(sort_from).position(|&m| m == hash_move) {
-                if h != sort_from {
-                    moves.swap(sort_from, h);
-                    sort_from += 1;
+        let in_check = board.is_in_check();
+        let is_pv = beta as i32 - alpha as i32 > 1;
+        let static_eval = board.get_pst_negamax_score();
+        if pv_idx > 0 && !is_pv && !in_check && beta.abs() < MAX_EVAL_SCORE {
+            // reverse futility pruning: far enough above beta that no move will bring us below it
+            if depth <= self.params.rfp_max_depth && static_eval - self.params.rfp_margin * depth as i16 >= beta {
+                self.pv_table[pv_idx].truncate(0);
+                return LowerBound(beta);
+            }
+            // razoring: far enough below alpha that only captures can help; the qsearch does not
+            // look at checks, so keep positions where a check could be a mate
+            if depth <= self.params.razoring_max_depth && static_eval + self.params.razoring_margin * depth as i16 <= alpha
+                && !Search::_has_check(board) {
+                let score = self._qsearch(board, pv_idx, alpha, beta);
+                if score.value() <= alpha {
+                    self.pv_table[pv_idx].truncate(0);
+                    return UpperBound(alpha);
                 }
             }
         }
-        if pv_idx < self.last_pv.len() {
-            let prev_pv_move = self.last_pv[pv_idx];
-            if let Some(pv) = moves.iter().skip(sort_from).position(|&m| m == prev_pv_move) {
-                if pv != sort_from {
-                    moves.swap(sort_from, pv);
-                    sort_from += 1;
-                }
+
+        if pv_idx > 0 {
+            if let Some(null_score) = self._null_move_search(board, tt, depth, pv_idx, beta) {
+                return null_score;
+            }
+        }
+
+        let mut moves = board.legal_moves();
+        if pv_idx == 0 {
+            if let Some(root_moves) = &self.root_moves {
+                moves.retain(|m| root_moves.contains(m));
             }
         }
-        // MVV-LVA
-        moves.as_mut_slice()[sort_from..].sort_by_key(|m| (-m.capture_value(), m.piece().value()));
+        if moves.is_empty() {
+            self.pv_table[pv_idx].truncate(0);
+            let score = if board.is_in_check() { -MATE_SCORE + pv_idx as i16 } else { 0 };
+            return Exact(score);
+        }
+
+        self._order_moves(board, tt, pv_idx, &mut moves);
+        let singular_move = self._singular_move(board, tt, &moves, depth, pv_idx);
 
         let mut alpha = alpha;
         let mut pv_move: Option<Move> = None;
 
-        for &move_ in moves.iter() {
+        // quiet moves can be pruned or reduced, unless they give check or we are in check
+        let can_prune = pv_idx > 0 && !is_pv && !in_check && alpha.abs() < MAX_EVAL_SCORE;
+        let futile = can_prune && depth <= self.params.futility_max_depth
+            && static_eval + self.params.futility_margin * depth as i16 <= alpha;
+        let late_moves = if can_prune && depth <= self.params.lmp_max_depth {
+            self.params.lmp_base + depth * depth
+        } else {
+            usize::MAX
+        };
+        let mut quiet_count = 0;
+        let mut searched_moves = 0;
+        let mut quiets_searched = vec![];
+
+        for (move_number, &move_) in moves.iter().enumerate() {
+            if pv_idx == 0 && self.info_callback.is_some() && self.time_manager.elapsed() >= CURRMOVE_MIN_TIME {
+                self._report(SearchInfo::CurrentMove { depth, move_, move_number: move_number + 1 });
+            }
+            let nodes_before = self.nodes;
+            let is_quiet_move = !move_.is_capture() && move_.promotion_piece().is_none();
+            let seventh_rank = if board.side_to_move() == Side::White { 6 } else { 1 };
+            let is_passed_pawn_push = move_.piece() == Piece::Pawn && board.is_passed_pawn(move_.from())
+                && move_.to().rank() == seventh_rank;
             board.push(&move_);
-            let score = -self._search(board, tt, depth - 1, pv_idx + 1, -beta, -alpha);
+            let gives_check = board.is_in_check();
+            let is_quiet = is_quiet_move && !in_check && !gives_check;
+            if is_quiet {
+                quiet_count += 1;
+                if futile || quiet_count > late_moves {
+                    board.pop();
+                    continue;
+                }
+            }
+
+            let reduction = if is_quiet && pv_idx > 0 && depth >= self.params.lmr_min_depth && move_number >= self.params.lmr_min_moves {
+                let reduction = self.params.lmr_reduction(depth, move_number) + if is_pv { 0 } else { 1 };
+                reduction.min(depth.saturating_sub(2))
+            } else {
+                0
+            };
+            // extend checks, passed pawns about to promote and singular moves by one ply
+            let extension = if pv_idx + depth < self.max_ply
+                && (gives_check || is_passed_pawn_push || Some(move_) == singular_move) { 1 } else { 0 };
+            let new_depth = depth - 1 + extension;
+            self.ply_moves[pv_idx] = Some(move_);
+            let score = if searched_moves == 0 {
+                -self._search(board, tt, new_depth, pv_idx + 1, -beta, -alpha)
+            } else {
+                // principal variation search: a zero window search, reduced for late quiet moves,
+                // shows that the move does not beat alpha; otherwise it is searched again in full
+                let mut score = -self._search(board, tt, new_depth - reduction, pv_idx + 1, -alpha - 1, -alpha);
+                if reduction > 0 && score.value() > alpha {
+                    score = -self._search(board, tt, new_depth, pv_idx + 1, -alpha - 1, -alpha);
+                }
+                if score.value() > alpha && score.value() < beta {
+                    score = -self._search(board, tt, new_depth, pv_idx + 1, -beta, -alpha);
+                }
+                score
+            };
+            searched_moves += 1;
             board.pop();
+            if self._is_stopped() {
+                return Exact(0);
+            }
+            if pv_idx == 0 {
+                self._add_root_move_nodes(move_, self.nodes - nodes_before);
+            }
             match score {
                 LowerBound(score) => {
                     // the node we've just searched was an all-node => this move is too good; we
                     // will never get the chance to play it since our opponent will never make the
                     // move that led to this position
                     self.cutoff_count += 1;
-                    tt.put(board, depth as u8, TTScore::Beta(score, move_));
+                    if is_quiet_move {
+                        self._update_quiet_heuristics(board.side_to_move(), pv_idx, depth, move_, &quiets_searched);
+                    }
+                    tt.put(board, depth as u8, pv_idx, TTScore::Beta(score, move_));
                     return LowerBound(score);
                 },
                 UpperBound(_) => {
@@ -337,10 +892,16 @@ impl Search {
             }
             if alpha >= beta {
                 self.cutoff_count += 1;
+                if is_quiet_move {
+                    self._update_quiet_heuristics(board.side_to_move(), pv_idx, depth, move_, &quiets_searched);
+                }
                 let score = LowerBound(beta);
-                tt.put(board, depth as u8, TTScore::Beta(beta, move_));
+                tt.put(board, depth as u8, pv_idx, TTScore::Beta(beta, move_));
                 return score;
             }
+            if is_quiet_move {
+                quiets_searched.push(move_);
+            }
         }
 
         let score = if let Some(pv_move) = pv_move {
@@ -352,41 +913,437 @@ impl Search {
                 let pv_tail = &tail[0];
                 pv.extend(pv_tail.iter());
             }
-            tt.put(board, depth as u8, TTScore::Pv(alpha, pv_move));
+            tt.put(board, depth as u8, pv_idx, TTScore::Pv(alpha, pv_move));
             Exact(alpha)
         }
         else {
-            tt.put(board, depth as u8, TTScore::Alpha(alpha));
+            tt.put(board, depth as u8, pv_idx, TTScore::Alpha(alpha));
             UpperBound(alpha)
         };
 
         score
     }
 
-    pub fn search(&mut self, board: &Board, tt: &mut TranspositionTable) -> Option<Move> {
+    // hash move first, then PV move from previous search, then captures and promotions in MVV/LVA
+    // order, then killer moves, the countermove and the other quiet moves by history score
+    fn _order_moves(&self, board: &Board, tt: &TranspositionTable, pv_idx: usize, moves: &mut [Move]) {
+        let hash_move = tt.get_move(board, moves);
+        let prev_pv_move = self.last_pv.get(pv_idx).copied();
+        let killers = self.killers[pv_idx];
+        let countermove = self._countermove(pv_idx);
+        let side = board.side_to_move();
+        moves.sort_by_cached_key(|&m| {
+            if Some(m) == hash_move {
+                (0, 0, 0)
+            } else if Some(m) == prev_pv_move {
+                (1, 0, 0)
+            } else if m.is_capture() || m.promotion_piece().is_some() {
+                (2, -m.capture_value() as i32, m.piece().value() as i32)
+            } else if Some(m) == killers[0] {
+                (3, 0, 0)
+            } else if Some(m) == killers[1] {
+                (4, 0, 0)
+            } else if Some(m) == countermove {
+                (5, 0, 0)
+            } else {
+                (6, -self.history[Search::_history_index(side, m)] - self._helper_jitter(m), 0)
+            }
+        });
+    }
+
+    fn _history_index(side: Side, move_: Move) -> usize {
+        let side = match side {
+            Side::White => 0,
+            Side::Black => 1,
+        };
+        (side * 64 + move_.from().ordinal as usize) * 64 + move_.to().ordinal as usize
+    }
+
+    fn _countermove_index(pv_idx: usize, ply_moves: &[Option<Move>]) -> Option<usize> {
+        let previous_move = ply_moves[pv_idx.checked_sub(1)?]?;
+        Some(previous_move.piece() as usize * 64 + previous_move.to().ordinal as usize)
+    }
+
+    fn _countermove(&self, pv_idx: usize) -> Option<Move> {
+        self.countermoves[Search::_countermove_index(pv_idx, &self.ply_moves)?]
+    }
+
+    // a quiet move caused a beta cutoff: remember it as a killer and countermove, and move its
+    // history score up and those of the quiet moves searched before it down
+    fn _update_quiet_heuristics(&mut self, side: Side, pv_idx: usize, depth: usize, move_: Move, quiets_searched: &[Move]) {
+        let killers = &mut self.killers[pv_idx];
+        if killers[0] != Some(move_) {
+            killers[1] = killers[0];
+            killers[0] = Some(move_);
+        }
+        if let Some(idx) = Search::_countermove_index(pv_idx, &self.ply_moves) {
+            self.countermoves[idx] = Some(move_);
+        }
+        let bonus = ((depth * depth) as i32).min(HISTORY_MAX);
+        self._update_history(Search::_history_index(side, move_), bonus);
+        for &quiet in quiets_searched {
+            self._update_history(Search::_history_index(side, quiet), -bonus);
+        }
+    }
+
+    fn _update_history(&mut self, idx: usize, bonus: i32) {
+        // scaled down as it gets closer to the maximum, so that it never exceeds it
+        let history = &mut self.history[idx];
+        *history += bonus - *history * bonus.abs() / HISTORY_MAX;
+    }
+
+    // The hash move, if searching the other moves with half the depth shows that they are all
+    // clearly worse than its TT score
+    fn _singular_move(&mut self, board: &mut Board, tt: &TranspositionTable, moves: &[Move], depth: usize, pv_idx: usize) -> Option<Move> {
+        if pv_idx == 0 || depth < self.params.singular_min_depth || pv_idx + depth >= self.max_ply {
+            return None;
+        }
+        let (tt_move, tt_score, tt_depth) = tt.get_move_with_score(board, pv_idx, moves)?;
+        if (tt_depth as usize) + 3 < depth || tt_score.abs() >= MAX_EVAL_SCORE || !moves.contains(&tt_move) {
+            return None;
+        }
+        let singular_beta = tt_score - self.params.singular_margin * depth as i16;
+        let mut is_singular = true;
+        for &move_ in moves.iter().filter(|&&m| m != tt_move) {
+            self.ply_moves[pv_idx] = Some(move_);
+            board.push(&move_);
+            let score = -self._search(board, tt, (depth - 1) / 2, pv_idx + 1, -singular_beta, -singular_beta + 1);
+            board.pop();
+            if score.value() >= singular_beta {
+                is_singular = false;
+                break;
+            }
+        }
+        self.pv_table[pv_idx + 1].truncate(0);
+        if is_singular { Some(tt_move) } else { None }
+    }
+
+    fn _has_check(board: &mut Board) -> bool {
+        board.legal_moves().iter().any(|move_| {
+            board.push(move_);
+            let is_check = board.is_in_check();
+            board.pop();
+            is_check
+        })
+    }
+
+    // Passes the turn and searches with reduced depth; if the opponent still cannot get below
+    // beta, the node fails high without searching its moves
+    fn _null_move_search(&mut self, board: &mut Board, tt: &TranspositionTable, depth: usize, pv_idx: usize, beta: i16) -> Option<Score> {
+        // passing is often the best move when only pawns are left (zugzwang), and is illegal in check
+        if depth < 2 || pv_idx < self.null_move_min_ply || beta.abs() >= MAX_EVAL_SCORE
+            || !board.has_non_pawn_material(board.side_to_move()) || board.is_in_check()
+            || board.get_pst_negamax_score() < beta {
+            return None;
+        }
+        let reduction = if depth > 6 { 3 } else { 2 };
+        let null_depth = depth.saturating_sub(reduction + 1);
+
+        // no two null moves in a row
+        let null_move_min_ply = self.null_move_min_ply;
+        self.null_move_min_ply = pv_idx + 2;
+        self.ply_moves[pv_idx] = None;
+        board.push_null();
+        let null_score = -self._search(board, tt, null_depth, pv_idx + 1, -beta, -beta + 1);
+        board.pop_null();
+        self.pv_table[pv_idx + 1].truncate(0);
+        self.null_move_min_ply = null_move_min_ply;
+        if null_score.value() < beta {
+            return None;
+        }
+
+        if depth >= NULL_MOVE_VERIFICATION_DEPTH {
+            // verify the fail high with null moves disabled for the first plies
+            self.null_move_min_ply = pv_idx + 3 * (depth - reduction) / 4;
+            let score = self._search(board, tt, depth - reduction, pv_idx, beta - 1, beta);
+            self.null_move_min_ply = null_move_min_ply;
+            if score.value() < beta {
+                return None;
+            }
+        }
+        self.null_cutoff_count += 1;
+        self.pv_table[pv_idx].truncate(0);
+        Some(LowerBound(beta))
+    }
+
+    // a fixed bonus of 0 to HELPER_JITTER for each quiet move, different in each helper thread, so
+    // that the helpers order quiet moves differently until their history scores take over
+    fn _helper_jitter(&self, move_: Move) -> i32 {
+        if self.helper_id == 0 {
+            return 0;
+        }
+        let hash = (PackedMove::pack(&move_).0 as u32 ^ (self.helper_id as u32).wrapping_mul(0x85eb_ca6b)).wrapping_mul(0x9e37_79b9);
+        (hash >> 23) as i32 % HELPER_JITTER
+    }
+
+    // whether a helper thread leaves out the iteration of this depth
+    fn _skips_depth(&self, depth: usize) -> bool {
+        if self.helper_id == 0 {
+            return false;
+        }
+        let i = (self.helper_id - 1) % SKIP_SIZE.len();
+        (depth + SKIP_PHASE[i]) / SKIP_SIZE[i] % 2 == 1
+    }
+
+    // helpers search to the same depth limit as the main thread, skipping some of the iterations
+    fn _helper(&self, helper_id: usize, stop: &Arc<AtomicBool>) -> Search {
+        let rng_seed = if self.rng_seed == 0 { 0 } else { self.rng_seed + helper_id as u64 };
+        let mut helper = Search::new_with_rng(self.max_depth, rng_seed);
+        helper.params = self.params.clone();
+        helper.tablebase = self.tablebase.clone();
+        helper.endgame_tables = self.endgame_tables.clone();
+        helper.root_moves = self.root_moves.clone();
+        helper.limits = self.limits.clone();
+        helper.helper_id = helper_id;
+        helper.stop = stop.clone();
+        helper.total_nodes = self.total_nodes.clone();
+        helper
+    }
+
+    pub fn search(&mut self, board: &Board, tt: &TranspositionTable) -> Option<Move> {
         let mut board = board.clone();
-        for i in 0..self.max_depth {
+        // in tablebase positions only search the moves that preserve the best DTM or DTZ outcome
+        self.root_moves = self.endgame_tables.as_ref().and_then(|endgame_tables| endgame_tables.root_moves(&mut board));
+        if self.root_moves.is_none() {
+            self.root_moves = self.tablebase.as_ref().and_then(|tablebase| tablebase.root_moves(&mut board));
+        }
+        if self.root_moves.is_some() {
+            self.tb_hits += 1;
+        }
+        if !self.limits.searchmoves.is_empty() {
+            let searchmoves = self.limits.searchmoves.clone();
+            self.root_moves = match self.root_moves.take() {
+                Some(root_moves) if root_moves.iter().any(|m| searchmoves.contains(m)) => {
+                    Some(root_moves.into_iter().filter(|m| searchmoves.contains(m)).collect())
+                },
+                _ => Some(searchmoves),
+            };
+        }
+        self.time_manager = TimeManager::new(&self.limits, board.side_to_move());
+        self.aborted = false;
+        tt.new_search();
+        if self.threads == 1 {
+            self._iterative_deepening(&mut board, tt);
+        } else {
+            self._parallel_search(&mut board, tt);
+        }
+        // stopped before the first iteration completed
+        if self.last_pv.is_empty() {
+            self.last_pv = self.root_moves.clone().unwrap_or_else(|| board.legal_moves()).into_iter().take(1).collect();
+        }
+        self.last_pv.first().copied()
+    }
+
+    fn _parallel_search(&mut self, board: &mut Board, tt: &TranspositionTable) {
+        let helpers_stop = Arc::new(AtomicBool::new(false));
+        let helpers = (1..self.threads).map(|helper_id| self._helper(helper_id, &helpers_stop)).collect_vec();
+        let helpers = std::thread::scope(|scope| {
+            let handles = helpers.into_iter().map(|mut helper| {
+                let mut board = board.clone();
+                scope.spawn(move || {
+                    helper._iterative_deepening(&mut board, tt);
+                    helper
+                })
+            }).collect_vec();
+            self._iterative_deepening(board, tt);
+            helpers_stop.store(true, Ordering::Relaxed);
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect_vec()
+        });
+        for helper in helpers.iter() {
+            self.eval_count += helper.eval_count;
+            self.cutoff_count += helper.cutoff_count;
+            self.q_cutoff_count += helper.q_cutoff_count;
+            self.tt_hit_count += helper.tt_hit_count;
+            self.tb_hits += helper.tb_hits;
+            self.null_cutoff_count += helper.null_cutoff_count;
+        }
+        let best_id = self._vote(&helpers);
+        if best_id > 0 {
+            let best = &helpers[best_id - 1];
+            self.last_score = best.last_score;
+            self.last_pv = best.last_pv.clone();
+            self.last_depth = best.last_depth;
+            self.seldepth = best.seldepth;
+            // the main thread's reports are about another move; all threads have counted their nodes
+            self._report_iteration(tt, self.last_depth, self.last_score, self.last_pv.clone(), self.nodes());
+        }
+    }
+
+    // every thread votes for its best move with a weight growing with its score and completed depth;
+    // the id of the deepest thread voting for the winning move, which provides the result
+    fn _vote(&self, helpers: &[Search]) -> usize {
+        let threads = std::iter::once(self).chain(helpers.iter()).filter(|t| !t.last_pv.is_empty()).collect_vec();
+        let Some(min_score) = threads.iter().map(|t| t.last_score.value() as i32).min() else { return 0 };
+        let votes = |move_: Move| -> i32 {
+            threads.iter()
+                .filter(|t| t.last_pv[0] == move_)
+                .map(|t| (t.last_score.value() as i32 - min_score + 14) * t.last_depth as i32)
+                .sum()
+        };
+        threads.iter()
+            .max_by_key(|t| (votes(t.last_pv[0]), t.last_depth, -(t.helper_id as i64)))
+            .map_or(0, |t| t.helper_id)
+    }
+
+    fn _iterative_deepening(&mut self, board: &mut Board, tt: &TranspositionTable) {
+        self._deepen(board, tt);
+        self.total_nodes.fetch_add(self.nodes % LIMITS_CHECK_NODES, Ordering::Relaxed);
+    }
+
+    fn _deepen(&mut self, board: &mut Board, tt: &TranspositionTable) {
+        let mut score: i16 = 0;
+        let max_depth = self.limits.depth.map_or(self.max_depth, |depth| depth.clamp(1, self.max_depth));
+        let root_move_count = self.root_moves.as_ref().map_or_else(|| board.legal_moves().len(), |root_moves| root_moves.len());
+        // how often the best move changed, halved every iteration
+        let mut best_move_changes = 0.0;
+        for i in 0..max_depth {
             let search_depth = i + 1;
-            let alpha = -i16::MAX;
-            let beta = i16::MAX;
-            self._search(&mut board, tt, search_depth, 0, alpha, beta);
+            if self._skips_depth(search_depth) {
+                continue;
+            }
+            let iteration_start = self.time_manager.elapsed();
+            let previous_score = score;
+            self.root_move_nodes.clear();
+            self.seldepth = 0;
+            // aspiration window around the previous iteration's score, widened on fail low or high
+            let mut delta = self.params.aspiration_window as i32;
+            let (mut alpha, mut beta) = if delta > 0 && search_depth >= self.params.aspiration_min_depth && score.abs() < MAX_EVAL_SCORE {
+                ((score as i32 - delta).max(-i16::MAX as i32) as i16, (score as i32 + delta).min(i16::MAX as i32) as i16)
+            } else {
+                (-i16::MAX, i16::MAX)
+            };
+            loop {
+                match self._search(board, tt, search_depth, 0, alpha, beta) {
+                    _ if self._is_stopped() => return,
+                    bound @ UpperBound(s) if alpha > -i16::MAX => {
+                        self._report_bound(tt, search_depth, bound);
+                        alpha = (s as i32 - delta).max(-i16::MAX as i32) as i16;
+                    },
+                    bound @ LowerBound(s) if beta < i16::MAX => {
+                        self._report_bound(tt, search_depth, bound);
+                        beta = (s as i32 + delta).min(i16::MAX as i32) as i16;
+                    },
+                    result => {
+                        score = result.value();
+                        self.last_score = result;
+                        break;
+                    }
+                }
+                delta *= 2;
+            }
+            let best_move_changed = self.last_pv.first().is_some_and(|m| self.pv_table[0].first() != Some(m));
+            best_move_changes = best_move_changes / 2.0 + if best_move_changed { 1.0 } else { 0.0 };
             self.last_pv = self.pv_table[0].clone();
-            for i in 0..self.max_depth {
-                self.pv_table[i].truncate(0);
+            self.last_depth = search_depth;
+            for pv in self.pv_table.iter_mut() {
+                pv.truncate(0);
+            }
+            self._report_iteration(tt, search_depth, self.last_score, self.last_pv.clone(), self._nodes_so_far());
+            if self.helper_id == 0 {
+                let score_drop = if search_depth > 1 { previous_score.saturating_sub(score) } else { 0 };
+                self._adjust_time(best_move_changes, score_drop);
+                // with a single legal reply there is nothing to think about
+                if root_move_count == 1 && self.time_manager.is_clock_limited() && !self.pondering.load(Ordering::Relaxed) {
+                    return;
+                }
+                if !self._can_deepen(self.time_manager.elapsed() - iteration_start) {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn _report(&mut self, info: SearchInfo) {
+        if let Some(info_callback) = self.info_callback.as_mut() {
+            info_callback(&info);
+        }
+    }
+
+    // the nodes searched so far, with those of this thread not yet counted in the total
+    fn _nodes_so_far(&self) -> u64 {
+        self.nodes() + self.nodes % LIMITS_CHECK_NODES
+    }
+
+    fn _report_iteration(&mut self, tt: &TranspositionTable, depth: usize, score: Score, pv: Vec<Move>, nodes: u64) {
+        if self.info_callback.is_none() {
+            return;
+        }
+        let info = SearchInfo::Iteration {
+            depth,
+            seldepth: self.seldepth,
+            score,
+            nodes,
+            time: self.time_manager.elapsed(),
+            hashfull: tt.hashfull(),
+            tbhits: self.tb_hits,
+            pv,
+        };
+        self._report(info);
+    }
+
+    // an iteration outside the aspiration window; its line may be incomplete, then the last one is
+    // reported instead
+    fn _report_bound(&mut self, tt: &TranspositionTable, depth: usize, score: Score) {
+        let pv = if self.pv_table[0].is_empty() { self.last_pv.clone() } else { self.pv_table[0].clone() };
+        self._report_iteration(tt, depth, score, pv, self._nodes_so_far());
+    }
+
+    fn _add_root_move_nodes(&mut self, move_: Move, nodes: u64) {
+        match self.root_move_nodes.iter_mut().find(|(m, _)| *m == move_) {
+            Some((_, move_nodes)) => *move_nodes += nodes,
+            None => self.root_move_nodes.push((move_, nodes)),
+        }
+    }
+
+    // the share of the nodes of the last iteration spent on its best move
+    fn _best_move_node_share(&self) -> Option<f64> {
+        let best_move = self.last_pv.first()?;
+        let total: u64 = self.root_move_nodes.iter().map(|(_, nodes)| nodes).sum();
+        let best: u64 = self.root_move_nodes.iter().filter(|(m, _)| m == best_move).map(|(_, nodes)| nodes).sum();
+        (total > 0).then(|| best as f64 / total as f64)
+    }
+
+    // gives the main thread more time when the best move is unstable or the score drops, and less
+    // when the best move dominates; an iteration answered from the TT leaves the time as it is
+    fn _adjust_time(&mut self, best_move_changes: f64, score_drop: i16) {
+        if let Some(best_move_node_share) = self._best_move_node_share() {
+            self.time_manager.adjust(best_move_changes, score_drop, best_move_node_share);
+        }
+    }
+
+    // whether the main thread starts another iteration after one that took the given time
+    fn _can_deepen(&self, last_iteration: Duration) -> bool {
+        if let (Some(mate), Some(mate_in)) = (self.limits.mate, self.last_score.mate_in()) {
+            if mate_in > 0 && mate_in as u32 <= mate {
+                return false;
             }
         }
-        self.last_pv.first().map(|&m| m)
+        if self.limits.nodes.is_some_and(|nodes| self.total_nodes.load(Ordering::Relaxed) >= nodes) {
+            return false;
+        }
+        self.pondering.load(Ordering::Relaxed) || self.time_manager.can_start_iteration(last_iteration)
+    }
+
+    // the depth of the last completed iteration
+    pub fn get_depth(&self) -> usize {
+        self.last_depth
     }
 
     pub fn get_pv(&self) -> Vec<Move> {
         self.last_pv.clone()
     }
+
+    // the score of the last completed iteration, from the point of view of the side to move
+    pub fn get_score(&self) -> Score {
+        self.last_score
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chessica::board::Outcome;
     use test_case::test_case;
 
     #[test_case("kr5r/p7/8/8/8/1R2Q3/6q1/KR6 w - - 0 1", 3, "e3a7" ; "mate in two #1")]
@@ -398,13 +1355,13 @@ mod tests {
     #[test_case("R6R/1r3pp1/4p1kp/3pP3/1r2qPP1/7P/1P1Q3K/8 w - - 1 0", 5, "f4f5" ; "mate in three #6")]
     #[test_case("4r1k1/5bpp/2p5/3pr3/8/1B3pPq/PPR2P2/2R2QK1 b - - 0 1", 5, "e5e1" ; "mate in three #7")]
     #[test_case("2r5/2p2k1p/pqp1RB2/2r5/PbQ2N2/1P3PP1/2P3P1/4R2K w - - 1 0", 5, "e6e7" ; "mate in three #8")]
-    // #[test_case("3rr1k1/pp3ppp/3b4/2p5/2Q5/6qP/PPP1B1P1/R1B2K1R b - - 0 1", 7, "g3e1" ; "mate in four #1")]
+    #[test_case("3rr1k1/pp3ppp/3b4/2p5/2Q5/6qP/PPP1B1P1/R1B2K1R b - - 0 1", 7, "g3e1" ; "mate in four #1")]
     fn test_finds_mate(initial_fen: &str, max_depth: usize, uci_move: &str) {
-        const TT_BITS: u8 = 24;
+        const TT_SIZE_MB: usize = 64;
         let board = Board::parse_fen(initial_fen).unwrap();
-        let mut tt = TranspositionTable::new(TT_BITS);
+        let tt = TranspositionTable::new(TT_SIZE_MB);
         let mut search = Search::new_with_rng(max_depth, 342044983);
-        let best_move = search.search(&board, &mut tt);
+        let best_move = search.search(&board, &tt);
         let pv = search.get_pv();
         let pv_str = pv.iter().map(|&m| m.to_uci_string()).collect::<Vec<String>>().join(", ");
         println!("PV: {}", pv_str);
@@ -418,13 +1375,180 @@ mod tests {
         }
     }
 
+    #[test_case("4rrk1/pppb4/7p/3P2pq/3Q4/P5P1/1PP2nKP/R3RNN1 b - - 0 1", 5, "d7h3" ; "mate in three #1")]
+    #[test_case("r5rk/5p1p/5R2/4B3/8/8/7P/7K w - - 0 1", 5, "f6a6" ; "mate in three #2")]
+    #[test_case("3rr1k1/pp3ppp/3b4/2p5/2Q5/6qP/PPP1B1P1/R1B2K1R b - - 0 1", 7, "g3e1" ; "mate in four #1")]
+    fn test_finds_mate_with_threads(initial_fen: &str, max_depth: usize, uci_move: &str) {
+        let board = Board::parse_fen(initial_fen).unwrap();
+        let tt = TranspositionTable::new(16);
+        let mut search = Search::new(max_depth);
+        search.set_threads(4);
+        let best_move = search.search(&board, &tt).unwrap();
+        assert_eq!(best_move.to_uci_string(), uci_move);
+        assert!(search.get_score().mate_in().is_some());
+        let mut single_thread_search = Search::new(max_depth);
+        single_thread_search.search(&board, &TranspositionTable::new(16));
+        println!("Eval count: {} (single thread: {})", search.eval_count, single_thread_search.eval_count);
+    }
+
+    #[test]
+    fn test_helpers_keep_depth_limit() {
+        let mut board = Board::starting_position();
+        let mut search = Search::new(5);
+        search.set_limits(SearchLimits { depth: Some(3), ..Default::default() });
+        // helper 1 leaves out the odd depths, helper 2 the even ones
+        for (helper_id, expected_depth) in [(1, 2), (2, 3)] {
+            let mut helper = search._helper(helper_id, &Arc::new(AtomicBool::new(false)));
+            helper._iterative_deepening(&mut board, &TranspositionTable::new(16));
+            assert_eq!(helper.get_depth(), expected_depth);
+        }
+    }
+
+    #[test_case(0, &[1, 2, 3, 4, 5, 6, 7, 8])]
+    #[test_case(1, &[2, 4, 6, 8])]
+    #[test_case(2, &[1, 3, 5, 7])]
+    #[test_case(3, &[1, 4, 5, 8])]
+    #[test_case(7, &[1, 2, 6, 7, 8])]
+    fn test_helpers_skip_depths(helper_id: usize, expected_depths: &[usize]) {
+        let mut search = Search::new(8);
+        search.helper_id = helper_id;
+        let depths = (1..=8).filter(|&depth| !search._skips_depth(depth)).collect_vec();
+        assert_eq!(depths, expected_depths);
+    }
+
+    #[test]
+    fn test_helpers_order_quiet_moves_differently() {
+        let board = Board::starting_position();
+        let tt = TranspositionTable::new(16);
+        let ordering = |helper_id: usize| {
+            let mut search = Search::new(4);
+            search.helper_id = helper_id;
+            let mut moves = board.legal_moves();
+            search._order_moves(&board, &tt, 1, &mut moves);
+            moves
+        };
+        let orderings = (0..4).map(ordering).collect_vec();
+        assert!(orderings.iter().tuple_combinations().all(|(a, b)| a != b));
+        assert_eq!(orderings[0], ordering(0));
+    }
+
+    #[test]
+    fn test_stopped_search_returns_move() {
+        let board = Board::starting_position();
+        let mut search = Search::new(5);
+        search.set_stop_flag(Arc::new(AtomicBool::new(true)));
+        let best_move = search.search(&board, &TranspositionTable::new(1));
+        assert!(best_move.is_some_and(|m| board.legal_moves().contains(&m)));
+        assert_eq!(search.last_depth, 0);
+    }
+
+    #[test]
+    fn test_stops_at_node_limit() {
+        let board = Board::parse_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let mut search = Search::new(MAX_DEPTH);
+        search.set_limits(SearchLimits { nodes: Some(20_000), ..Default::default() });
+        assert!(search.search(&board, &TranspositionTable::new(16)).is_some());
+        assert!(search.nodes() >= 20_000 && search.nodes() < 20_000 + 2 * LIMITS_CHECK_NODES);
+        assert!(search.get_depth() > 1 && search.get_depth() < MAX_DEPTH);
+    }
+
+    #[test]
+    fn test_single_reply_stops_at_depth_one() {
+        let board = Board::parse_fen("k7/8/8/8/8/8/r7/K6r w - - 0 1").unwrap();
+        let mut search = Search::new(MAX_DEPTH);
+        search.set_limits(SearchLimits { wtime: Some(60_000), ..Default::default() });
+        assert_eq!(search.search(&board, &TranspositionTable::new(16)), Some(find_move(&board, "a1a2")));
+        assert_eq!(search.get_depth(), 1);
+    }
+
+    #[test]
+    fn test_best_move_node_share() {
+        let board = Board::parse_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let mut search = Search::new(4);
+        search.search(&board, &TranspositionTable::new(16));
+        assert_eq!(search.root_move_nodes.len(), board.legal_moves().len());
+        let share = search._best_move_node_share().unwrap();
+        assert!(share > 0.0 && share < 1.0);
+    }
+
+    #[test]
+    fn test_reports_iterations() {
+        let board = Board::parse_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let infos = Arc::new(std::sync::Mutex::new(vec![]));
+        let mut search = Search::new(4);
+        let reported = infos.clone();
+        search.set_info_callback(move |info| reported.lock().unwrap().push(info.clone()));
+        search.search(&board, &TranspositionTable::new(16));
+        let infos = infos.lock().unwrap();
+        let exact = infos.iter().filter_map(|info| match info {
+            SearchInfo::Iteration { depth, seldepth, score: Exact(_), nodes, pv, .. } => Some((*depth, *seldepth, *nodes, pv.clone())),
+            _ => None,
+        }).collect_vec();
+        assert_eq!(exact.iter().map(|(depth, ..)| *depth).collect_vec(), vec![1, 2, 3, 4]);
+        assert!(exact.iter().all(|(depth, seldepth, ..)| seldepth >= depth));
+        assert!(exact.windows(2).all(|w| w[0].2 < w[1].2));
+        assert_eq!(exact[3].3, search.get_pv());
+    }
+
+    #[test]
+    fn test_reports_tablebase_hits() {
+        let mut endgame_tables = EndgameTables::new();
+        endgame_tables.generate("KQvK").unwrap();
+        let board = Board::parse_fen("8/8/8/3k4/8/8/8/K1Q5 w - - 0 1").unwrap();
+        let tbhits = Arc::new(std::sync::Mutex::new(vec![]));
+        let mut search = Search::new(2);
+        search.set_endgame_tables(Arc::new(endgame_tables));
+        let reported = tbhits.clone();
+        search.set_info_callback(move |info| if let SearchInfo::Iteration { tbhits, .. } = info {
+            reported.lock().unwrap().push(*tbhits);
+        });
+        search.search(&board, &TranspositionTable::new(16));
+        let tbhits = tbhits.lock().unwrap();
+        assert!(!tbhits.is_empty() && tbhits.iter().all(|&hits| hits > 0));
+        assert_eq!(tbhits.last().copied(), Some(search.tb_hits()));
+    }
+
+    #[test]
+    fn test_votes_for_best_move() {
+        let board = Board::starting_position();
+        let thread = |helper_id: usize, uci_move: &str, score: i16, depth: usize| {
+            let mut search = Search::new(6);
+            search.helper_id = helper_id;
+            search.last_pv = vec![find_move(&board, uci_move)];
+            search.last_score = Exact(score);
+            search.last_depth = depth;
+            search
+        };
+        let main = thread(0, "e2e4", 30, 5);
+        assert_eq!(main._vote(&[thread(1, "d2d4", 50, 6), thread(2, "d2d4", 40, 6), thread(3, "e2e4", 20, 6)]), 1);
+        assert_eq!(main._vote(&[thread(1, "d2d4", 50, 6), thread(2, "e2e4", 40, 6)]), 2);
+        // helpers that did not complete an iteration have no vote
+        assert_eq!(main._vote(&[Search::new(6)]), 0);
+    }
+
+    #[test]
+    fn test_tt_ignores_torn_entries() {
+        let board = Board::starting_position();
+        let mut other_board = Board::starting_position();
+        other_board.push(&find_move(&board, "e2e4"));
+        let tt = TranspositionTable::new(0);
+        tt.put(&board, 1, 0, TTScore::Pv(10, find_move(&board, "d2d4")));
+        tt.put(&other_board, 1, 0, TTScore::Pv(20, find_move(&other_board, "e7e5")));
+        assert!(tt.get(&board, 1, 0, 0, 0).is_some());
+        // the data of one entry written over by another thread, but not yet its key
+        let entries = &tt.buckets[0].entries;
+        entries[0].data.store(entries[1].data.load(Ordering::Relaxed), Ordering::Relaxed);
+        assert!(tt.get(&board, 1, 0, 0, 0).is_none());
+        assert!(tt.get(&other_board, 1, 0, 0, 0).is_some());
+    }
+
     #[test]
     fn test_treats_passed_pawns_with_respect() {
         let mut board = Board::parse_fen("5N2/1k6/7p/8/1p6/2p4P/6PK/R7 w - - 0 37").unwrap();
         // black has two very dangerous passed pawns; qsearch should ensure we don't ignore the threat
         let mut search = Search::new(7);
-        let mut tt = TranspositionTable::new(20);
-        match search.search(&board, &mut tt) {
+        let tt = TranspositionTable::new(16);
+        match search.search(&board, &tt) {
             Some(best_move) => {
                 let pv = search.get_pv();
                 let pv_str = pv.iter().map(|&m| m.to_uci_string()).collect::<Vec<String>>().join(", ");
@@ -441,68 +1565,253 @@ mod tests {
         // white is up a knight
         let mut board = Board::parse_fen("5k2/3R4/8/3N1p1p/8/6KP/3r1P2/8 w - - 2 62").unwrap();
 
-        // white moves king from g3 to f3
-        {
-            let mut search = Search::new(5);
-            let mut tt = TranspositionTable::new(20);
-            match search.search(&board, &mut tt) {
-                Some(best_move) => {
-                    assert_eq!(best_move.to_uci_string(), "g3f3");
-                    board.push(&best_move);
-                }
-                None => assert!(false)
-            };
+        // white shuffles the king between g3 and f3, black the rook between d2 and d4
+        for uci_move in ["g3f3", "d2d4", "f3g3", "d4d2"] {
+            board.push_uci(uci_move).unwrap();
         }
 
-        // black moves rook from d2 to d4
+        // *** we are now in the same position we started in ***
+
+        // white moves king from g3 to f3 again
+        board.push_uci("g3f3").unwrap();
+
+        // black moves rook from d2 to d4 again (playing for a draw by repetition, since they are down a knight)
         board.push_uci("d2d4").unwrap();
 
-        // white moves king back from f3 to g3
+        // if white now moves king from f3 to g3 again, black will simply move their rook from d4 back to d2, and claim draw by repetition
+        // so instead, white moves king elsewhere (e.g. from f3 to e2 or g2)
         {
             let mut search = Search::new(5);
-            let mut tt = TranspositionTable::new(20);
-            match search.search(&board, &mut tt) {
+            let tt = TranspositionTable::new(16);
+            match search.search(&board, &tt) {
                 Some(best_move) => {
-                    assert_eq!(best_move.to_uci_string(), "f3g3");
+                    assert_ne!(best_move.to_uci_string(), "f3g3");
                     board.push(&best_move);
+                    board.push_uci("d4d2").unwrap();
+                    assert!(!board.is_draw_by_threefold_repetition());
                 }
                 None => assert!(false)
             };
         }
+    }
 
-        // black moves rook back from d4 to d2
-        board.push_uci("d4d2").unwrap();
+    // the real tables in chessica/tests/data/syzygy, or in the directories in CHESSICA_SYZYGY_PATH
+    #[test_case("8/8/8/4k3/8/8/8/1QK5 w - - 0 1" ; "KQvK")]
+    #[test_case("8/8/8/8/8/2k5/8/R3K3 w - - 0 1" ; "KRvK")]
+    #[test_case("8/4k3/8/8/8/8/3KBN2/8 w - - 0 1" ; "KBNvK")]
+    #[ignore = "needs the fixture tables listed in chessica/tests/data/syzygy/README.md"]
+    fn test_keeps_tablebase_win(fen: &str) {
+        let path = std::env::var("CHESSICA_SYZYGY_PATH")
+            .unwrap_or_else(|_| concat!(env!("CARGO_MANIFEST_DIR"), "/../chessica/tests/data/syzygy").to_string());
+        let tablebase = Arc::new(Tablebase::new(&path));
+        let mut board = Board::parse_fen(fen).unwrap();
+        let mut search = Search::new(3);
+        search.set_tablebase(tablebase.clone());
+        let best_move = search.search(&board, &TranspositionTable::new(16)).unwrap();
+        assert!(search.tb_hits() > 0);
+        board.push(&best_move);
+        assert_eq!(tablebase.probe_wdl(&mut board), Some(Wdl::Loss));
+    }
 
-        // *** we are now in the same position we started in ***
+    #[test_case("k1Q5/8/1K6/8/8/8/8/8 b - - 0 1", -MATE_SCORE + 3 ; "checkmate")]
+    #[test_case("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1", 0 ; "stalemate")]
+    #[test_case("8/8/4k3/8/8/3BK3/8/8 b - - 0 1", 0 ; "insufficient material")]
+    fn test_qsearch_scores_final_positions(fen: &str, expected_score: i16) {
+        let mut board = Board::parse_fen(fen).unwrap();
+        let mut search = Search::new(1);
+        assert_eq!(search._qsearch(&mut board, 3, -i16::MAX, i16::MAX).value(), expected_score);
+    }
 
-        // white moves king from g3 to f3 again
-        {
-            let mut search = Search::new(5);
-            let mut tt = TranspositionTable::new(20);
-            match search.search(&board, &mut tt) {
-                Some(best_move) => {
-                    assert_eq!(best_move.to_uci_string(), "g3f3");
-                    board.push(&best_move);
-                }
-                None => assert!(false)
-            };
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", true ; "starting position")]
+    #[test_case("4k3/pp6/8/8/8/8/PP6/4K3 w - - 0 1", false ; "pawn ending")]
+    fn test_null_move_pruning(fen: &str, expected_cutoffs: bool) {
+        let board = Board::parse_fen(fen).unwrap();
+        let mut search = Search::new(4);
+        search.search(&board, &TranspositionTable::new(16));
+        assert_eq!(search.null_cutoff_count > 0, expected_cutoffs);
+    }
+
+    #[test]
+    fn test_selective_search_evaluates_fewer_positions() {
+        let board = Board::parse_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8").unwrap();
+        let no_pruning = SearchParams {
+            lmr_min_depth: usize::MAX,
+            rfp_max_depth: 0,
+            futility_max_depth: 0,
+            razoring_max_depth: 2,
+            lmp_max_depth: 0,
+            ..SearchParams::default()
+        };
+        let mut full_search = Search::new(5);
+        full_search.set_params(no_pruning);
+        full_search.search(&board, &TranspositionTable::new(16));
+        let mut search = Search::new(5);
+        search.search(&board, &TranspositionTable::new(16));
+        assert!(search.eval_count < full_search.eval_count);
+    }
+
+    #[test]
+    fn test_reductions_at_shallow_depths() {
+        let board = Board::parse_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8").unwrap();
+        let mut search = Search::new(4);
+        search.set_params(SearchParams { lmr_min_depth: 0, lmr_min_moves: 0, ..SearchParams::default() });
+        assert!(search.search(&board, &TranspositionTable::new(16)).is_some());
+    }
+
+    #[test]
+    fn test_aspiration_windows_evaluate_fewer_positions() {
+        let board = Board::parse_fen("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8").unwrap();
+        let mut full_window_search = Search::new(6);
+        full_window_search.set_params(SearchParams { aspiration_window: 0, ..SearchParams::default() });
+        let full_window_move = full_window_search.search(&board, &TranspositionTable::new(16));
+        let mut search = Search::new(6);
+        let best_move = search.search(&board, &TranspositionTable::new(16));
+        println!("Eval count: {} (full window: {})", search.eval_count, full_window_search.eval_count);
+        println!("Cutoff count: {} (full window: {})", search.cutoff_count, full_window_search.cutoff_count);
+        assert!(search.eval_count < full_window_search.eval_count);
+        assert_eq!(best_move, full_window_move);
+    }
+
+    fn find_move(board: &Board, uci_move: &str) -> Move {
+        *board.legal_moves().iter().find(|m| m.to_uci_string() == uci_move).unwrap()
+    }
+
+    #[test]
+    fn test_orders_quiet_moves() {
+        let board = Board::parse_fen("4k3/8/8/3p4/8/8/8/R3K2N w - - 0 1").unwrap();
+        let previous_move = find_move(&Board::parse_fen("4k3/8/8/3p4/8/8/4K3/R6N b - - 0 1").unwrap(), "e8d7");
+        let mut search = Search::new(3);
+        search.killers[1][0] = Some(find_move(&board, "a1a7"));
+        search.ply_moves[0] = Some(previous_move);
+        let countermove_idx = Search::_countermove_index(1, &search.ply_moves).unwrap();
+        search.countermoves[countermove_idx] = Some(find_move(&board, "h1g3"));
+        search._update_history(Search::_history_index(Side::White, find_move(&board, "e1d2")), 100);
+
+        let mut moves = board.legal_moves();
+        search._order_moves(&board, &TranspositionTable::new(16), 1, &mut moves);
+        let first_moves: Vec<String> = moves.iter().take(3).map(|m| m.to_uci_string()).collect();
+        assert_eq!(first_moves, vec!["a1a7", "h1g3", "e1d2"]);
+    }
+
+    #[test_case("4k3/8/8/8/3r4/2P5/8/4K3 w - - 0 1", Some("c3d4") ; "recapture")]
+    #[test_case("4k3/8/8/8/8/2P5/8/4K3 w - - 0 1", None ; "quiet")]
+    fn test_singular_move(fen: &str, expected_move: Option<&str>) {
+        let mut board = Board::parse_fen(fen).unwrap();
+        let tt = TranspositionTable::new(16);
+        let mut search = Search::new(8);
+        search._search(&mut board, &tt, 5, 1, -i16::MAX, i16::MAX);
+        let moves = board.legal_moves();
+        let singular_move = search._singular_move(&mut board, &tt, &moves, 6, 1);
+        assert_eq!(singular_move.map(|m| m.to_uci_string()), expected_move.map(|m| m.to_string()));
+    }
+
+    #[test_case("kr5r/p7/8/8/8/1R2Q3/6q1/KR6 w - - 0 1", 3, Some(2) ; "mate in two")]
+    #[test_case("r5rk/5p1p/5R2/4B3/8/8/7P/7K w - - 0 1", 5, Some(3) ; "mate in three")]
+    #[test_case("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1", 5, Some(1) ; "mate in one found at depth five")]
+    #[test_case("k7/8/1K6/8/8/8/8/6Q1 b - - 0 1", 4, Some(-1) ; "mated in one")]
+    #[test_case("k7/8/2K5/8/8/8/8/6Q1 b - - 0 1", 6, Some(-2) ; "mated in two")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 3, None ; "no mate")]
+    fn test_reports_mate_distance(fen: &str, max_depth: usize, expected_mate: Option<i16>) {
+        let board = Board::parse_fen(fen).unwrap();
+        let mut search = Search::new(max_depth);
+        search.search(&board, &TranspositionTable::new(16));
+        assert_eq!(search.get_score().mate_in(), expected_mate);
+    }
+
+    #[test_case(Exact(35), "cp 35" ; "centipawns")]
+    #[test_case(Exact(MATE_SCORE - 5), "mate 3" ; "mate")]
+    #[test_case(Exact(-MATE_SCORE + 4), "mate -2" ; "mated")]
+    #[test_case(LowerBound(-20), "cp -20 lowerbound" ; "lower bound")]
+    #[test_case(UpperBound(MATE_SCORE - 1), "mate 1 upperbound" ; "upper bound")]
+    fn test_score_to_uci_string(score: Score, expected: &str) {
+        assert_eq!(score.to_uci_string(), expected);
+    }
+
+    #[test_case(MATE_SCORE - 7, 3 ; "mate")]
+    #[test_case(-MATE_SCORE + 6, 4 ; "mated")]
+    #[test_case(TB_WIN_SCORE - 5, 2 ; "tablebase win")]
+    #[test_case(150, 5 ; "centipawns")]
+    fn test_tt_mate_scores(score: i16, ply: usize) {
+        let board = Board::starting_position();
+        let tt = TranspositionTable::new(16);
+        tt.put(&board, 1, ply, TTScore::Pv(score, board.legal_moves()[0]));
+        assert_eq!(tt.get(&board, 1, ply, -i16::MAX, i16::MAX).unwrap().value(), score);
+        // the same position two plies further from the root is a mate or win two plies further away
+        let expected = if score.abs() < MAX_EVAL_SCORE { score } else { score - 2 * score.signum() };
+        assert_eq!(tt.get(&board, 1, ply + 2, -i16::MAX, i16::MAX).unwrap().value(), expected);
+    }
+
+    #[test]
+    fn test_tt_encodes_moves() {
+        let board = Board::parse_fen("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let moves = board.legal_moves();
+        let tt = TranspositionTable::new(1);
+        for &move_ in moves.iter() {
+            tt.put(&board, 1, 0, TTScore::Pv(0, move_));
+            assert_eq!(tt.get_move(&board, &moves), Some(move_));
+            assert_eq!(tt.get_pv_move(&board), Some(move_));
         }
+        // an upper bound keeps the move stored before
+        tt.put(&board, 2, 0, TTScore::Alpha(0));
+        assert_eq!(tt.get_move(&board, &moves), moves.last().copied());
+    }
 
-        // black moves rook from d2 to d4 again (playing for a draw by repetition, since they are down a knight)
-        board.push_uci("d2d4").unwrap();
+    #[test]
+    fn test_tt_replaces_shallow_and_old_entries() {
+        // a single bucket
+        let tt = TranspositionTable::new(0);
+        let mut positions = Vec::new();
+        for move_ in Board::starting_position().legal_moves().into_iter().take(6) {
+            let mut board = Board::starting_position();
+            board.push(&move_);
+            positions.push(board);
+        }
+        for (i, depth) in [5, 6, 7, 8].into_iter().enumerate() {
+            tt.put(&positions[i], depth, 0, TTScore::Alpha(0));
+        }
+        tt.put(&positions[4], 1, 0, TTScore::Alpha(0));
+        assert!(tt.get(&positions[0], 0, 0, 0, 0).is_none());
+        assert!(tt.get(&positions[4], 0, 0, 0, 0).is_some());
+        // entries of the previous search go before shallow entries of the current one
+        tt.new_search();
+        tt.put(&positions[0], 2, 0, TTScore::Alpha(0));
+        tt.put(&positions[5], 1, 0, TTScore::Alpha(0));
+        assert!(tt.get(&positions[0], 0, 0, 0, 0).is_some());
+        assert!(tt.get(&positions[1], 0, 0, 0, 0).is_none());
+        assert!(tt.get(&positions[3], 0, 0, 0, 0).is_some());
+    }
 
-        // if white now moves king from f3 to g3 again, black will simply move their rook from d4 back to d2, and claim draw by repetition
-        // so instead, white moves king from f3 to e2
-        {
-            let mut search = Search::new(5);
-            let mut tt = TranspositionTable::new(20);
-            match search.search(&board, &mut tt) {
-                Some(best_move) => {
-                    assert_eq!(best_move.to_uci_string(), "f3e2");
-                    board.push(&best_move);
-                }
-                None => assert!(false)
+    #[test]
+    fn test_tt_hashfull() {
+        let board = Board::starting_position();
+        let tt = TranspositionTable::new(0);
+        assert_eq!(tt.hashfull(), 0);
+        tt.put(&board, 1, 0, TTScore::Alpha(0));
+        assert_eq!(tt.hashfull(), 250);
+        tt.new_search();
+        assert_eq!(tt.hashfull(), 0);
+        tt.clear();
+        assert!(tt.get(&board, 0, 0, 0, 0).is_none());
+    }
+
+    #[test]
+    fn test_mates_with_endgame_tables() {
+        let mut endgame_tables = EndgameTables::new();
+        endgame_tables.generate("KQvK").unwrap();
+        let endgame_tables = Arc::new(endgame_tables);
+        let mut board = Board::parse_fen("8/8/8/3k4/8/8/8/K1Q5 w - - 0 1").unwrap();
+        let Some(Dtm::Win(plies)) = endgame_tables.probe(&board) else { panic!() };
+        let tt = TranspositionTable::new(16);
+        for _ in 0..plies {
+            let best_move = if board.side_to_move() == Side::White {
+                let mut search = Search::new(2);
+                search.set_endgame_tables(endgame_tables.clone());
+                search.search(&board, &tt).unwrap()
+            } else {
+                endgame_tables.root_moves(&mut board).unwrap()[0]
             };
+            board.push(&best_move);
         }
+        assert!(matches!(board.outcome(), Some(Outcome::Checkmate { .. })));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,154 @@
+use std::time::{Duration, Instant};
+use chessica::{Move, Side};
+
+// time kept back for the communication with the GUI on every move
+const MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+// the moves to the next time control assumed when the GUI does not tell
+const MOVES_TO_GO_DEFAULT: u32 = 30;
+// the next iteration is assumed to take at least this many times as long as the last one
+const BRANCHING_FACTOR: u32 = 2;
+// a score drop of this many centipawns since the last iteration doubles the soft limit
+const SCORE_DROP_MAX: i16 = 150;
+
+// The limits of a search as given by the UCI go command; times are in milliseconds
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchLimits {
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u32>,
+    pub movetime: Option<u64>,
+    pub depth: Option<usize>,
+    pub nodes: Option<u64>,
+    // stop once a mate in this many moves is found
+    pub mate: Option<u32>,
+    pub infinite: bool,
+    pub ponder: bool,
+    // only these moves are searched at the root, if any
+    pub searchmoves: Vec<Move>,
+}
+
+// The soft limit is the time the search should take: no new iteration is started after it. The
+// search is aborted at the hard limit, and no iteration is started that would not end before it.
+pub struct TimeManager {
+    start: Instant,
+    // the soft limit before it is adjusted to how the search goes; None for a fixed move time
+    base_soft_limit: Option<Duration>,
+    soft_limit: Option<Duration>,
+    hard_limit: Option<Duration>,
+}
+
+impl TimeManager {
+    pub fn new(limits: &SearchLimits, side: Side) -> Self {
+        let (time, increment) = match side {
+            Side::White => (limits.wtime, limits.winc),
+            Side::Black => (limits.btime, limits.binc),
+        };
+        let (base_soft_limit, soft_limit, hard_limit) = if let Some(movetime) = limits.movetime {
+            let movetime = Duration::from_millis(movetime).saturating_sub(MOVE_OVERHEAD).max(Duration::from_millis(1));
+            (None, Some(movetime), Some(movetime))
+        } else if let Some(time) = time {
+            let available = Duration::from_millis(time).saturating_sub(MOVE_OVERHEAD).max(Duration::from_millis(1));
+            let increment = Duration::from_millis(increment.unwrap_or(0));
+            let moves_to_go = limits.movestogo.unwrap_or(MOVES_TO_GO_DEFAULT).max(1);
+            let base = available / moves_to_go + increment * 3 / 4;
+            // never more than four times the base time or 80% of the clock on one move
+            let hard_limit = (base * 4).min(available * 4 / 5);
+            let soft_limit = base.min(hard_limit);
+            (Some(soft_limit), Some(soft_limit), Some(hard_limit))
+        } else {
+            (None, None, None)
+        };
+        TimeManager {
+            start: Instant::now(),
+            base_soft_limit,
+            soft_limit,
+            hard_limit,
+        }
+    }
+
+    // whether the search is limited by the clock, other than by a fixed move time
+    pub fn is_clock_limited(&self) -> bool {
+        self.base_soft_limit.is_some()
+    }
+
+    // Scales the soft limit after an iteration. It grows when the best move keeps changing (a
+    // decaying count of changes per iteration) or the score dropped, and shrinks when the best
+    // move took most of the nodes of the iteration, so that the others were refuted quickly.
+    pub fn adjust(&mut self, best_move_changes: f64, score_drop: i16, best_move_node_share: f64) {
+        let (Some(base_soft_limit), Some(hard_limit)) = (self.base_soft_limit, self.hard_limit) else { return };
+        let instability = 1.0 + best_move_changes;
+        let falling_score = 1.0 + score_drop.clamp(0, SCORE_DROP_MAX) as f64 / SCORE_DROP_MAX as f64;
+        let dominance = (1.6 - best_move_node_share).clamp(0.5, 1.5);
+        let soft_limit = base_soft_limit.mul_f64(instability * falling_score * dominance);
+        self.soft_limit = Some(soft_limit.min(hard_limit));
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn is_hard_limit_reached(&self) -> bool {
+        self.hard_limit.is_some_and(|hard_limit| self.elapsed() >= hard_limit)
+    }
+
+    // whether there is time for another iteration, given how long the last one took
+    pub fn can_start_iteration(&self, last_iteration: Duration) -> bool {
+        let elapsed = self.elapsed();
+        self.soft_limit.is_none_or(|soft_limit| elapsed < soft_limit)
+            && self.hard_limit.is_none_or(|hard_limit| elapsed + last_iteration * BRANCHING_FACTOR < hard_limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(SearchLimits { wtime: Some(60_000), ..Default::default() }, Side::White, Some(1_999), Some(7_996) ; "sudden death")]
+    #[test_case(SearchLimits { wtime: Some(60_000), btime: Some(10_030), binc: Some(1_000), ..Default::default() }, Side::Black, Some(1_083), Some(4_333) ; "increment")]
+    #[test_case(SearchLimits { btime: Some(10_030), movestogo: Some(1), ..Default::default() }, Side::Black, Some(8_000), Some(8_000) ; "last move before time control")]
+    #[test_case(SearchLimits { wtime: Some(60_000), movetime: Some(1_030), ..Default::default() }, Side::White, Some(1_000), Some(1_000) ; "move time")]
+    #[test_case(SearchLimits { btime: Some(60_000), depth: Some(8), ..Default::default() }, Side::White, None, None ; "other side's clock")]
+    fn test_time_limits(limits: SearchLimits, side: Side, soft_limit: Option<u64>, hard_limit: Option<u64>) {
+        let time_manager = TimeManager::new(&limits, side);
+        assert_eq!(time_manager.soft_limit.map(|t| t.as_millis() as u64), soft_limit);
+        assert_eq!(time_manager.hard_limit.map(|t| t.as_millis() as u64), hard_limit);
+    }
+
+    #[test]
+    fn test_adjust() {
+        let limits = SearchLimits { wtime: Some(60_030), ..Default::default() };
+        let mut time_manager = TimeManager::new(&limits, Side::White);
+        let soft_limit = |time_manager: &TimeManager| time_manager.soft_limit.unwrap().as_millis();
+        time_manager.adjust(0.0, 0, 0.6);
+        assert_eq!(soft_limit(&time_manager), 2_000);
+        // one move dominates
+        time_manager.adjust(0.0, 0, 0.95);
+        assert_eq!(soft_limit(&time_manager), 1_300);
+        // the best move changed in the last iteration and the one before
+        time_manager.adjust(1.5, 0, 0.6);
+        assert_eq!(soft_limit(&time_manager), 5_000);
+        time_manager.adjust(0.0, 75, 0.6);
+        assert_eq!(soft_limit(&time_manager), 3_000);
+        // never beyond the hard limit
+        time_manager.adjust(3.0, 300, 0.1);
+        assert_eq!(soft_limit(&time_manager), 8_000);
+        // a fixed move time is kept
+        let mut time_manager = TimeManager::new(&SearchLimits { movetime: Some(1_030), ..Default::default() }, Side::White);
+        time_manager.adjust(3.0, 300, 0.1);
+        assert_eq!(soft_limit(&time_manager), 1_000);
+        assert!(!time_manager.is_clock_limited());
+    }
+
+    #[test]
+    fn test_can_start_iteration() {
+        let time_manager = TimeManager::new(&SearchLimits { movetime: Some(1_030), ..Default::default() }, Side::White);
+        assert!(time_manager.can_start_iteration(Duration::from_millis(100)));
+        // the next iteration would take about a second
+        assert!(!time_manager.can_start_iteration(Duration::from_millis(500)));
+        assert!(!time_manager.is_hard_limit_reached());
+        assert!(TimeManager::new(&SearchLimits::default(), Side::White).can_start_iteration(Duration::from_secs(3600)));
+    }
+}
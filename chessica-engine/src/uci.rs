@@ -1,44 +1,76 @@
 use std::io::{BufRead, Write};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
 use log::{error, info, warn};
 use chessica::board::Board;
-use crate::search::{Search, TranspositionTable};
+use chessica::book::PolyglotBook;
+use chessica::endgame::EndgameTables;
+use chessica::syzygy::Tablebase;
+use chessica::Move;
+use crate::search::{Search, SearchInfo, TranspositionTable, MAX_DEPTH};
+use crate::time_manager::SearchLimits;
 
 pub struct UciSession {
     position: Board,
-    tt: TranspositionTable,
+    tt: Arc<TranspositionTable>,
     is_running: bool,
-    output: Box<dyn Write>
+    // the search runs on its own thread until it completes or is stopped; while pondering it
+    // holds back its best move until ponderhit or stop
+    search_thread: Option<JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+    pondering: Arc<AtomicBool>,
+    threads: usize,
+    chess960: bool,
+    own_book: bool,
+    book: Option<PolyglotBook>,
+    tablebase: Option<Arc<Tablebase>>,
+    endgame_tables: Option<Arc<EndgameTables>>,
+    output: Arc<Mutex<Box<dyn Write + Send>>>
 }
 
 const MAX_DEPTH_DEFAULT: usize = 5;
-const TT_BITS_DEFAULT: u8 = 24;
+const HASH_MB_DEFAULT: usize = 16;
+const HASH_MB_MAX: usize = 4096;
+const THREADS_MAX: usize = 256;
 
 impl UciSession {
 
-    pub fn new(output: Box<dyn Write>) -> Self {
+    pub fn new(output: Box<dyn Write + Send>) -> Self {
         UciSession {
             position: Board::starting_position(),
-            tt: TranspositionTable::new(TT_BITS_DEFAULT),
+            tt: Arc::new(TranspositionTable::new(HASH_MB_DEFAULT)),
             is_running: true,
-            output
+            search_thread: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            pondering: Arc::new(AtomicBool::new(false)),
+            threads: 1,
+            chess960: false,
+            own_book: false,
+            book: None,
+            tablebase: None,
+            endgame_tables: None,
+            output: Arc::new(Mutex::new(output))
         }
     }
 
     pub fn run(&mut self, input: &mut Box<dyn BufRead>) {
         for line in input.lines() {
-            if !self.is_running {
-                break;
-            }
             match line {
                 Ok(command) => { self.handle_command(&command); },
                 Err(_) => { break; }
             }
+            if !self.is_running {
+                return;
+            }
         }
+        // the input ended without quit, so let a search started last complete
+        self.wait_for_search();
     }
 
-    fn write(&mut self, line: &str) {
-        info!(">>> {}", line);
-        self.output.write(line.as_bytes()).unwrap();
+    fn write(&self, line: &str) {
+        write_output(&self.output, line);
     }
 
     fn handle_command(&mut self, command: &String) {
@@ -48,6 +80,13 @@ impl UciSession {
             "uci" => {
                 self.write("id name Chessica 0.2\n");
                 self.write("id author Dan P\n");
+                self.write(format!("option name Hash type spin default {} min 1 max {}\n", HASH_MB_DEFAULT, HASH_MB_MAX).as_str());
+                self.write(format!("option name Threads type spin default 1 min 1 max {}\n", THREADS_MAX).as_str());
+                self.write("option name UCI_Chess960 type check default false\n");
+                self.write("option name OwnBook type check default false\n");
+                self.write("option name BookFile type string default <empty>\n");
+                self.write("option name SyzygyPath type string default <empty>\n");
+                self.write("option name EndgamePath type string default <empty>\n");
                 self.write("uciok\n");
             },
             "isready" => {
@@ -63,12 +102,13 @@ impl UciSession {
                 self.handle_position_command(&tokens[1..]);
             },
             "go" => {
-                self.handle_go_command();
+                self.handle_go_command(&tokens[1..]);
             },
             "stop" => {
                 self.handle_stop_command();
             },
             "ponderhit" => {
+                self.handle_ponderhit_command();
             },
             "quit" => {
                 self.handle_quit_command();
@@ -77,63 +117,473 @@ impl UciSession {
                 warn!("Unknown command: {}", tokens[0]);
             }
         }
-        self.output.flush().unwrap()
+        self.output.lock().unwrap().flush().unwrap()
     }
 
-    fn handle_setoption_command(&mut self, _args: &[&str]) {
+    fn handle_setoption_command(&mut self, args: &[&str]) {
+        if args.first() != Some(&"name") {
+            warn!("Malformed setoption command: {}", args.join(" "));
+            return;
+        }
+        let value_index = args.iter().position(|&arg| arg == "value").unwrap_or(args.len());
+        let name = args[1..value_index].join(" ");
+        let value = args.get(value_index + 1..).map(|v| v.join(" ")).unwrap_or_default();
+        match name.to_lowercase().as_str() {
+            "hash" => {
+                match value.parse::<usize>() {
+                    Ok(size_mb) => {
+                        self.tt = Arc::new(TranspositionTable::new(size_mb.clamp(1, HASH_MB_MAX)));
+                    },
+                    Err(_) => {
+                        error!("Invalid hash size: {}", value);
+                    }
+                }
+            },
+            "threads" => {
+                match value.parse::<usize>() {
+                    Ok(threads) => {
+                        self.threads = threads.clamp(1, THREADS_MAX);
+                    },
+                    Err(_) => {
+                        error!("Invalid number of threads: {}", value);
+                    }
+                }
+            },
+            "uci_chess960" => {
+                self.chess960 = value == "true";
+            },
+            "ownbook" => {
+                self.own_book = value == "true";
+            },
+            "bookfile" => {
+                self.book = None;
+                if value.is_empty() || value == "<empty>" {
+                    return;
+                }
+                match PolyglotBook::open(&value) {
+                    Ok(book) => {
+                        info!("Loaded {} book entries from {}", book.len(), value);
+                        self.book = Some(book);
+                    },
+                    Err(e) => {
+                        error!("Cannot open book file {}: {}", value, e);
+                    }
+                }
+            },
+            "syzygypath" => {
+                self.tablebase = None;
+                if value.is_empty() || value == "<empty>" {
+                    return;
+                }
+                let tablebase = Tablebase::new(&value);
+                if tablebase.max_pieces() == 0 {
+                    error!("No Syzygy tables found in {}", value);
+                    return;
+                }
+                info!("Found Syzygy tables for up to {} pieces in {}", tablebase.max_pieces(), value);
+                self.tablebase = Some(Arc::new(tablebase));
+            },
+            "endgamepath" => {
+                self.endgame_tables = None;
+                if value.is_empty() || value == "<empty>" {
+                    return;
+                }
+                match EndgameTables::open_dir(&value) {
+                    Ok(endgame_tables) => {
+                        info!("Loaded endgame tables {} from {}", endgame_tables.names().join(", "), value);
+                        self.endgame_tables = Some(Arc::new(endgame_tables));
+                    },
+                    Err(e) => {
+                        error!("Cannot open endgame tables in {}: {}", value, e);
+                    }
+                }
+            },
+            _ => {
+                warn!("Unknown option: {}", name);
+            }
+        }
     }
 
     fn handle_ucinewgame_command(&mut self) {
+        self.stop_search();
         self.position = Board::starting_position();
         self.tt.clear();
     }
 
     fn handle_position_command(&mut self, args: &[&str]) {
-        match args[0] {
-            "startpos" => {
-                let mut position = Board::starting_position();
-                if args.len() > 1 && args[1] == "moves" {
-                    for &uci_move in args[2..].iter() {
-                        if let Err(_) = position.push_uci(uci_move) {
-                            let fen = position.to_fen_string();
-                            error!("Illegal move {} in position {}", uci_move, fen);
-                            return;
-                        }
+        let moves_index = args.iter().position(|&arg| arg == "moves").unwrap_or(args.len());
+        let mut position = match args[0] {
+            "startpos" => Board::starting_position(),
+            "fen" => {
+                let fen = args[1..moves_index].join(" ");
+                match Board::parse_fen_strict(fen.as_str()) {
+                    Ok(position) => position,
+                    Err(e) => {
+                        error!("Invalid FEN {}: {}", fen, e);
+                        return;
                     }
                 }
-                self.position = position;
             },
-            "fen" => {
-                let fen = args[1..].join(" ");
-                match Board::parse_fen(fen.as_str()) {
-                    Ok(position) => {
-                        self.position = position;
+            _ => {
+                return;
+            }
+        };
+        if self.chess960 {
+            position.set_chess960(true);
+        }
+        for &uci_move in args.iter().skip(moves_index + 1) {
+            if position.push_uci(uci_move).is_err() {
+                let fen = position.to_fen_string();
+                error!("Illegal move {} in position {}", uci_move, fen);
+                return;
+            }
+        }
+        self.position = position;
+    }
+
+    fn parse_go_args(&self, args: &[&str]) -> SearchLimits {
+        fn parse_value<T: FromStr>(value: Option<&&str>) -> Option<T> {
+            let value = value?;
+            let parsed = value.parse().ok();
+            if parsed.is_none() {
+                warn!("Invalid go parameter value: {}", value);
+            }
+            parsed
+        }
+        // GUIs may send a negative time left when the clock has run out
+        fn parse_time(value: Option<&&str>) -> Option<u64> {
+            parse_value::<i64>(value).map(|time| time.max(0) as u64)
+        }
+        let mut limits = SearchLimits::default();
+        let mut tokens = args.iter().peekable();
+        while let Some(&token) = tokens.next() {
+            match token {
+                "wtime" => limits.wtime = parse_time(tokens.next()),
+                "btime" => limits.btime = parse_time(tokens.next()),
+                "winc" => limits.winc = parse_time(tokens.next()),
+                "binc" => limits.binc = parse_time(tokens.next()),
+                "movestogo" => limits.movestogo = parse_value(tokens.next()),
+                "movetime" => limits.movetime = parse_time(tokens.next()),
+                "depth" => limits.depth = parse_value(tokens.next()),
+                "nodes" => limits.nodes = parse_value(tokens.next()),
+                "mate" => limits.mate = parse_value(tokens.next()),
+                "infinite" => limits.infinite = true,
+                "ponder" => limits.ponder = true,
+                "searchmoves" => {
+                    while let Some(move_) = tokens.peek().and_then(|uci_move| self.position.get_uci_move(uci_move).ok()) {
+                        limits.searchmoves.push(move_);
+                        tokens.next();
                     }
-                    Err(_) => {
-                        error!("Failed to parse FEN: {}", fen);
+                },
+                "" => {},
+                _ => warn!("Unknown go parameter: {}", token)
+            }
+        }
+        limits
+    }
+
+    fn handle_go_command(&mut self, args: &[&str]) {
+        self.stop_search();
+        let limits = self.parse_go_args(args);
+        // the best move of a pondering or infinite search must wait for ponderhit or stop
+        if !limits.ponder && !limits.infinite {
+            if let Some(book_move) = self.book_move() {
+                self.write_bestmove(&book_move);
+                return;
+            }
+        }
+        let max_depth = match limits.depth {
+            Some(depth) => depth.clamp(1, MAX_DEPTH),
+            // a go without limits searches to a fixed depth
+            None if SearchLimits { ponder: false, ..limits.clone() } == SearchLimits::default() => MAX_DEPTH_DEFAULT,
+            None => MAX_DEPTH,
+        };
+        let mut search = Search::new(max_depth);
+        search.set_threads(self.threads);
+        search.set_stop_flag(self.stop.clone());
+        search.set_ponder_flag(self.pondering.clone());
+        if let Some(tablebase) = &self.tablebase {
+            search.set_tablebase(tablebase.clone());
+        }
+        if let Some(endgame_tables) = &self.endgame_tables {
+            search.set_endgame_tables(endgame_tables.clone());
+        }
+        self.stop.store(false, Ordering::Relaxed);
+        self.pondering.store(limits.ponder, Ordering::Relaxed);
+        // an infinite search also holds back its best move until stop
+        let infinite = limits.infinite;
+        search.set_limits(limits);
+        let position = self.position.clone();
+        let tt = self.tt.clone();
+        let output = self.output.clone();
+        let stop = self.stop.clone();
+        let pondering = self.pondering.clone();
+        let chess960 = self.chess960;
+        let info_output = self.output.clone();
+        search.set_info_callback(move |info| write_output(&info_output, info_string(info, chess960).as_str()));
+        self.search_thread = Some(std::thread::spawn(move || {
+            let best_move = search.search(&position, &tt);
+            while (infinite || pondering.load(Ordering::Relaxed)) && !stop.load(Ordering::Relaxed) {
+                std::thread::park();
+            }
+            match best_move {
+                Some(best_move) => {
+                    write_output(&output, format!("bestmove {}\n", uci_move_string(&best_move, chess960)).as_str());
+                },
+                None => {
+                    if let Some(outcome) = position.outcome() {
+                        warn!("No legal moves in position {} ({:?})", position.to_fen_string(), outcome);
                     }
+                    write_output(&output, "bestmove 0000\n");
                 }
-            },
-            _ => {}
+            }
+            output.lock().unwrap().flush().unwrap();
+        }));
+    }
+
+    fn book_move(&self) -> Option<Move> {
+        if !self.own_book {
+            return None;
         }
+        let book_move = self.book.as_ref()?.weighted_move(&self.position, &mut rand::thread_rng())?;
+        info!("Book move {}", book_move.to_uci_string());
+        Some(book_move)
     }
 
-    fn handle_go_command(&mut self) {
-        let mut search = Search::new(MAX_DEPTH_DEFAULT);
-        match search.search(&self.position, &mut self.tt) {
-            Some(best_move) => {
-                self.write(format!("bestmove {}\n", best_move.to_uci_string()).as_str());
-            },
-            None => {
-                // checkmate/stalemate
-            }
+    fn write_bestmove(&self, best_move: &Move) {
+        let uci_move = uci_move_string(best_move, self.chess960);
+        self.write(format!("bestmove {}\n", uci_move).as_str());
+    }
+
+    // a stopped search sends the best move of its last completed iteration
+    fn stop_search(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.wait_for_search();
+    }
+
+    fn wait_for_search(&mut self) {
+        if let Some(search_thread) = self.search_thread.take() {
+            search_thread.thread().unpark();
+            search_thread.join().unwrap();
         }
     }
 
     fn handle_stop_command(&mut self) {
+        self.stop_search();
+    }
+
+    fn handle_ponderhit_command(&mut self) {
+        self.pondering.store(false, Ordering::Relaxed);
+        if let Some(search_thread) = &self.search_thread {
+            search_thread.thread().unpark();
+        }
     }
 
     fn handle_quit_command(&mut self) {
-        std::process::exit(0);
+        self.stop_search();
+        self.is_running = false;
+    }
+}
+
+fn write_output(output: &Mutex<Box<dyn Write + Send>>, line: &str) {
+    info!(">>> {}", line);
+    output.lock().unwrap().write_all(line.as_bytes()).unwrap();
+}
+
+fn info_string(info: &SearchInfo, chess960: bool) -> String {
+    match info {
+        SearchInfo::Iteration { depth, seldepth, score, nodes, time, hashfull, tbhits, pv } => {
+            let nps = (*nodes as u128 * 1000 / time.as_millis().max(1)) as u64;
+            let pv: Vec<String> = pv.iter().map(|m| uci_move_string(m, chess960)).collect();
+            format!("info depth {} seldepth {} score {} nodes {} nps {} time {} hashfull {} tbhits {} pv {}\n",
+                depth, seldepth, score.to_uci_string(), nodes, nps, time.as_millis(), hashfull, tbhits, pv.join(" "))
+        },
+        SearchInfo::CurrentMove { depth, move_, move_number } => {
+            format!("info depth {} currmove {} currmovenumber {}\n", depth, uci_move_string(move_, chess960), move_number)
+        },
+    }
+}
+
+fn uci_move_string(move_: &Move, chess960: bool) -> String {
+    if chess960 {
+        move_.to_uci_string_chess960()
+    } else {
+        move_.to_uci_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chessica::book::BookEntry;
+    use crate::search::Score::{Exact, LowerBound};
+    use std::time::Duration;
+    use test_case::test_case;
+    use std::io::Cursor;
+
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedOutput {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_go_plays_book_move() {
+        let board = Board::parse_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        // e1h1, castling as encoded by Polyglot
+        let entry = BookEntry { key: board.hash(), raw_move: 4 << 6 | 7, weight: 1, learn: 0 };
+        let path = std::env::temp_dir().join(format!("chessica-test-{}.bin", std::process::id()));
+        std::fs::write(&path, entry.to_bytes()).unwrap();
+
+        let output = SharedOutput::default();
+        let mut session = UciSession::new(Box::new(output.clone()));
+        session.handle_command(&"setoption name OwnBook value true".to_string());
+        session.handle_command(&format!("setoption name BookFile value {}", path.display()));
+        session.handle_command(&"position fen 4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1".to_string());
+        session.handle_command(&"go".to_string());
+        assert_eq!(output.contents(), "bestmove e1g1\n");
+
+        // a pondering search does not play from the book, its best move waits for stop
+        session.handle_command(&"go ponder".to_string());
+        std::fs::remove_file(&path).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(output.contents().matches("bestmove ").count(), 1);
+        session.handle_command(&"stop".to_string());
+        assert_eq!(output.contents().matches("bestmove ").count(), 2);
+    }
+
+    #[test]
+    fn test_go_reports_mate_score() {
+        let output = SharedOutput::default();
+        let mut session = UciSession::new(Box::new(output.clone()));
+        session.handle_command(&"position fen k7/8/1K6/8/8/8/8/6Q1 w - - 0 1".to_string());
+        session.handle_command(&"go".to_string());
+        session.wait_for_search();
+
+        let output = output.contents();
+        let lines = output.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), MAX_DEPTH_DEFAULT + 1);
+        for (i, line) in lines[..MAX_DEPTH_DEFAULT].iter().enumerate() {
+            assert!(line.starts_with(&format!("info depth {} seldepth ", i + 1)), "{}", line);
+            assert!(line.contains(" score mate 1 nodes ") && line.ends_with(" pv g1g8"), "{}", line);
+        }
+        assert_eq!(lines[MAX_DEPTH_DEFAULT], "bestmove g1g8");
+    }
+
+    #[test_case(SearchInfo::Iteration {
+        depth: 7, seldepth: 12, score: Exact(35), nodes: 150_000, time: Duration::from_millis(300), hashfull: 42, tbhits: 7,
+        pv: vec![
+            Board::starting_position().get_uci_move("e2e4").unwrap(),
+            Board::parse_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap().get_uci_move("e7e5").unwrap(),
+        ],
+    }, "info depth 7 seldepth 12 score cp 35 nodes 150000 nps 500000 time 300 hashfull 42 tbhits 7 pv e2e4 e7e5\n" ; "iteration")]
+    #[test_case(SearchInfo::Iteration {
+        depth: 1, seldepth: 1, score: LowerBound(-20), nodes: 20, time: Duration::ZERO, hashfull: 0, tbhits: 0,
+        pv: vec![Board::starting_position().get_uci_move("g1f3").unwrap()],
+    }, "info depth 1 seldepth 1 score cp -20 lowerbound nodes 20 nps 20000 time 0 hashfull 0 tbhits 0 pv g1f3\n" ; "bound")]
+    #[test_case(SearchInfo::CurrentMove {
+        depth: 20, move_: Board::starting_position().get_uci_move("d2d4").unwrap(), move_number: 3,
+    }, "info depth 20 currmove d2d4 currmovenumber 3\n" ; "current move")]
+    fn test_info_string(info: SearchInfo, expected: &str) {
+        assert_eq!(info_string(&info, false), expected);
+    }
+
+    #[test_case("wtime 60000 btime 55000 winc 1000 binc 2000 movestogo 20", SearchLimits {
+        wtime: Some(60_000), btime: Some(55_000), winc: Some(1_000), binc: Some(2_000), movestogo: Some(20), ..Default::default()
+    } ; "clock")]
+    #[test_case("wtime -250 btime 1000", SearchLimits { wtime: Some(0), btime: Some(1_000), ..Default::default() } ; "time running out")]
+    #[test_case("movetime 500 depth 6 nodes 100000", SearchLimits {
+        movetime: Some(500), depth: Some(6), nodes: Some(100_000), ..Default::default()
+    } ; "fixed limits")]
+    #[test_case("ponder mate 3", SearchLimits { ponder: true, mate: Some(3), ..Default::default() } ; "ponder for a mate")]
+    #[test_case("searchmoves e2e4 g1f3 infinite", SearchLimits {
+        searchmoves: vec![Board::starting_position().get_uci_move("e2e4").unwrap(), Board::starting_position().get_uci_move("g1f3").unwrap()],
+        infinite: true,
+        ..Default::default()
+    } ; "searchmoves")]
+    fn test_parse_go_args(args: &str, expected_limits: SearchLimits) {
+        let session = UciSession::new(Box::new(SharedOutput::default()));
+        let args = args.split(" ").collect::<Vec<&str>>();
+        assert_eq!(session.parse_go_args(&args), expected_limits);
+    }
+
+    #[test_case("position startpos", "go depth 2", "info depth 2 seldepth " ; "depth")]
+    #[test_case("position fen k7/8/1K6/8/8/8/8/6Q1 w - - 0 1", "go mate 1", "score mate 1 nodes " ; "mate")]
+    #[test_case("position startpos", "go depth 3 searchmoves a2a3", "bestmove a2a3\n" ; "searchmoves")]
+    fn test_go_limits(position: &str, go: &str, expected_output: &str) {
+        let output = SharedOutput::default();
+        let mut session = UciSession::new(Box::new(output.clone()));
+        session.handle_command(&position.to_string());
+        session.handle_command(&go.to_string());
+        session.wait_for_search();
+        assert!(output.contents().contains(expected_output), "{}", output.contents());
+    }
+
+    #[test]
+    fn test_go_movetime() {
+        let output = SharedOutput::default();
+        let mut session = UciSession::new(Box::new(output.clone()));
+        session.handle_command(&"position startpos".to_string());
+        let start = std::time::Instant::now();
+        session.handle_command(&"go movetime 200".to_string());
+        session.wait_for_search();
+        assert!(start.elapsed() < std::time::Duration::from_millis(400));
+        assert!(output.contents().contains("bestmove "));
+    }
+
+    #[test]
+    fn test_go_infinite_waits_for_stop() {
+        let output = SharedOutput::default();
+        let mut session = UciSession::new(Box::new(output.clone()));
+        session.handle_command(&"position fen k7/8/1K6/8/8/8/8/6Q1 w - - 0 1".to_string());
+        session.handle_command(&"go infinite".to_string());
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert!(!output.contents().contains("bestmove"));
+        session.handle_command(&"stop".to_string());
+        assert!(output.contents().ends_with("bestmove g1g8\n"));
+    }
+
+    #[test]
+    fn test_ponder_holds_back_best_move() {
+        let output = SharedOutput::default();
+        let mut session = UciSession::new(Box::new(output.clone()));
+        session.handle_command(&"position fen k7/8/1K6/8/8/8/8/6Q1 w - - 0 1".to_string());
+        session.handle_command(&"go ponder".to_string());
+        // the search is over long before this, but the best move waits for ponderhit
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        assert!(!output.contents().contains("bestmove"));
+        session.handle_command(&"ponderhit".to_string());
+        session.wait_for_search();
+        assert!(output.contents().ends_with("bestmove g1g8\n"));
+
+        // stopping right away still gives a best move
+        session.handle_command(&"go ponder".to_string());
+        session.handle_command(&"stop".to_string());
+        assert_eq!(output.contents().matches("bestmove ").count(), 2);
+        assert!(output.contents().lines().last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn test_quit_stops_search() {
+        let output = SharedOutput::default();
+        let mut session = UciSession::new(Box::new(output.clone()));
+        let mut input: Box<dyn BufRead> = Box::new(Cursor::new("position startpos\ngo ponder\nquit\nisready\n"));
+        session.run(&mut input);
+        let output = output.contents();
+        assert!(output.ends_with("\n") && output.lines().last().unwrap().starts_with("bestmove "));
+        assert!(!output.contains("readyok"));
     }
 }
@@ -1,3 +1,4 @@
+use enum_map::{enum_map, EnumMap};
 use lazy_static::lazy_static;
 use regex::Regex;
 use string_builder::Builder;
@@ -7,10 +8,10 @@ use crate::magic::{build_magic_bishop_tables, build_magic_rook_tables, MagicBitB
 use crate::square::Square;
 use crate::zobrist::ZobristHash;
 use crate::Move::{EnPassantCapture, LongCastling, Promotion, Regular, ShortCastling};
-use crate::{sq, EnPassantCaptureMove, Move, Piece, PromotionMove, RegularMove, Side};
-use crate::errors::{FenParseError, IllegalMoveError};
+use crate::{sq, CastlingMove, EnPassantCaptureMove, Move, Piece, PromotionMove, RegularMove, Side};
+use crate::errors::{FenField, FenParseError, IllegalMoveError, IllegalPositionError, SanParseError};
 use crate::history::History;
-use crate::masks::{BLACK_PASSED_PAWN_ZONE, WHITE_PASSED_PAWN_ZONE};
+use crate::masks::{BLACK_PASSED_PAWN_ZONE, LIGHT_SQUARES, WHITE_PASSED_PAWN_ZONE};
 use crate::pst::PstEvaluator;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -19,16 +20,18 @@ struct MoveUndoInfo {
     half_move_clock: u8,
     ep_square: Option<Square>,
     is_threefold_repetition: bool,
+    is_fivefold_repetition: bool,
     passed_pawns: BitBoard
 }
 
 impl MoveUndoInfo {
-    fn new(castling_rights: u8, half_move_clock: u8, ep_square: Option<Square>, is_threefold_repetition: bool, passed_pawns: BitBoard) -> Self {
+    fn new(castling_rights: u8, half_move_clock: u8, ep_square: Option<Square>, is_threefold_repetition: bool, is_fivefold_repetition: bool, passed_pawns: BitBoard) -> Self {
         MoveUndoInfo {
             castling_rights,
             half_move_clock,
             ep_square,
             is_threefold_repetition,
+            is_fivefold_repetition,
             passed_pawns
         }
     }
@@ -40,6 +43,31 @@ struct Checks {
     check_blocking_squares: BitBoard,
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Outcome {
+    Checkmate { winner: Side },
+    Stalemate,
+    InsufficientMaterial,
+    FivefoldRepetition,
+    SeventyFiveMoveRule,
+    // the remaining outcomes are draws that a player may claim, but which do not end the game automatically
+    ThreefoldRepetition,
+    FiftyMoveRule,
+}
+
+impl Outcome {
+    pub fn winner(&self) -> Option<Side> {
+        match self {
+            Outcome::Checkmate { winner } => Some(*winner),
+            _ => None,
+        }
+    }
+
+    pub fn is_claimable(&self) -> bool {
+        matches!(self, Outcome::ThreefoldRepetition | Outcome::FiftyMoveRule)
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Board {
     side_to_move: Side,
@@ -54,14 +82,19 @@ pub struct Board {
     kings: BitBoard,
     passed_pawns: BitBoard,
     castling_rights: u8,
+    short_castling_rook_file: EnumMap<Side, u8>,
+    long_castling_rook_file: EnumMap<Side, u8>,
+    chess960: bool,
     half_move_clock: u8,
     full_move_number: u16,
     ep_square: Option<Square>,
     z_hash: ZobristHash,
     pst_eval: PstEvaluator,
     move_stack: Vec<(Move, MoveUndoInfo)>,
+    null_move_stack: Vec<MoveUndoInfo>,
     hash_history: History,
-    is_threefold_repetition: bool
+    is_threefold_repetition: bool,
+    is_fivefold_repetition: bool
 }
 
 impl Board {
@@ -107,14 +140,19 @@ impl Board {
             kings: BitBoard::from_squares(&[sq!(e1), sq!(e8)]),
             passed_pawns: BitBoard::empty(),
             castling_rights: 0xf,
+            short_castling_rook_file: enum_map! { _ => 7 },
+            long_castling_rook_file: enum_map! { _ => 0 },
+            chess960: false,
             half_move_clock: 0,
             full_move_number: 1,
             ep_square: None,
             z_hash: ZobristHash::new(),
             pst_eval: PstEvaluator::new(),
             move_stack: vec![],
+            null_move_stack: vec![],
             hash_history: History::new(),
-            is_threefold_repetition: false
+            is_threefold_repetition: false,
+            is_fivefold_repetition: false
         };
         board.init_zobrist_hash();
         board.init_pst_eval();
@@ -123,99 +161,326 @@ impl Board {
     }
 
     pub fn parse_fen(fen: &str) -> Result<Self, FenParseError> {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(
-                r"([RNBQKPrnbqkp1-8/]{15,}) ([wb]) (K?Q?k?q?|-) ([a-h]?[1-8]?|-) (\d+) (\d+)"
-            )
-            .unwrap();
-        }
-        if let Some(captures) = RE.captures(fen) {
-            let rows = captures[1].rsplit('/').collect::<Vec<_>>();
-            if rows.len() != 8 {
-                return Err(FenParseError);
-            }
-
-            let (side_to_move, side_to_not_move) = if &captures[2] == "w" {
-                (Side::White, Side::Black)
-            } else {
-                (Side::Black, Side::White)
-            };
-            let castling_rights_str = &captures[3];
-
-            let mut castling_rights: u8 = 0;
-            if castling_rights_str.contains("K") {
-                castling_rights |= Board::short_castling_flag(Side::White);
-            }
-            if castling_rights_str.contains("Q") {
-                castling_rights |= Board::long_castling_flag(Side::White);
-            }
-            if castling_rights_str.contains("k") {
-                castling_rights |= Board::short_castling_flag(Side::Black);
-            }
-            if castling_rights_str.contains("q") {
-                castling_rights |= Board::long_castling_flag(Side::Black);
+        let fields = fen.split_whitespace().collect::<Vec<&str>>();
+        match fields.len() {
+            0 => return Err(FenParseError::MissingField(FenField::PiecePlacement)),
+            1 => return Err(FenParseError::MissingField(FenField::SideToMove)),
+            2 => return Err(FenParseError::MissingField(FenField::CastlingRights)),
+            3 => return Err(FenParseError::MissingField(FenField::EnPassantSquare)),
+            4 => return Err(FenParseError::MissingField(FenField::HalfMoveClock)),
+            5 => return Err(FenParseError::MissingField(FenField::FullMoveNumber)),
+            6 => {}
+            n => return Err(FenParseError::TooManyFields(n)),
+        };
+        let half_move_clock: u8 = fields[4].parse().map_err(|_| FenParseError::InvalidField {
+            field: FenField::HalfMoveClock,
+            reason: format!("'{}' is not a number between 0 and 255", fields[4]),
+        })?;
+        let full_move_number: u16 = fields[5].parse().map_err(|_| FenParseError::InvalidField {
+            field: FenField::FullMoveNumber,
+            reason: format!("'{}' is not a number between 0 and 65535", fields[5]),
+        })?;
+        Board::from_fen_fields(fields[0], fields[1], fields[2], fields[3], half_move_clock, full_move_number)
+    }
+
+    pub fn parse_fen_strict(fen: &str) -> Result<Self, FenParseError> {
+        let board = Board::parse_fen(fen)?;
+        board.validate().map_err(FenParseError::IllegalPosition)?;
+        Ok(board)
+    }
+
+    pub(crate) fn from_fen_fields(
+        piece_placement: &str,
+        side_to_move: &str,
+        castling_rights: &str,
+        ep_square: &str,
+        half_move_clock: u8,
+        full_move_number: u16,
+    ) -> Result<Self, FenParseError> {
+        let rows = piece_placement.rsplit('/').collect::<Vec<_>>();
+        if rows.len() != 8 {
+            return Err(FenParseError::InvalidField {
+                field: FenField::PiecePlacement,
+                reason: format!("expected 8 ranks, found {}", rows.len()),
+            });
+        }
+
+        let (side_to_move, side_to_not_move) = match side_to_move {
+            "w" => (Side::White, Side::Black),
+            "b" => (Side::Black, Side::White),
+            _ => {
+                return Err(FenParseError::InvalidField {
+                    field: FenField::SideToMove,
+                    reason: format!("expected 'w' or 'b', found '{}'", side_to_move),
+                })
             }
+        };
 
-            let ep_square_spec = &captures[4];
-            let ep_square: Option<Square> = match ep_square_spec {
-                "-" => None,
-                _ => ep_square_spec.parse().ok(),
-            };
+        // castling rights are resolved once the pieces are on the board, since X-FEN and
+        // Shredder-FEN identify the castling rook relative to the king
+        let castling_rights = match castling_rights {
+            "-" => "",
+            _ => castling_rights,
+        };
+        if let Some(c) = castling_rights.chars().find(|c| !matches!(c, 'K' | 'Q' | 'k' | 'q' | 'A'..='H' | 'a'..='h')) {
+            return Err(FenParseError::InvalidField {
+                field: FenField::CastlingRights,
+                reason: format!("unexpected character '{}'", c),
+            });
+        }
+
+        let ep_square: Option<Square> = match ep_square {
+            "-" => None,
+            _ => match ep_square.parse::<Square>() {
+                Ok(square) if square.rank() == 2 || square.rank() == 5 => Some(square),
+                _ => {
+                    return Err(FenParseError::InvalidField {
+                        field: FenField::EnPassantSquare,
+                        reason: format!("'{}' is not a square on the third or sixth rank", ep_square),
+                    })
+                }
+            },
+        };
 
-            let half_move_clock: u8 = captures[5].parse().unwrap();
-            let full_move_number: u16 = captures[6].parse().unwrap();
-
-            let mut board = Board {
-                side_to_move,
-                side_to_not_move,
-                white_pieces: BitBoard::empty(),
-                black_pieces: BitBoard::empty(),
-                pawns: BitBoard::empty(),
-                bishops: BitBoard::empty(),
-                knights: BitBoard::empty(),
-                rooks: BitBoard::empty(),
-                queens: BitBoard::empty(),
-                kings: BitBoard::empty(),
-                passed_pawns: BitBoard::empty(),
-                castling_rights,
-                half_move_clock,
-                full_move_number,
-                ep_square,
-                z_hash: ZobristHash::new(),
-                pst_eval: PstEvaluator::new(),
-                move_stack: vec![],
-                hash_history: History::new(),
-                is_threefold_repetition: false
-            };
-            board.init_zobrist_hash();
-            board.init_pst_eval();
+        let mut board = Board {
+            side_to_move,
+            side_to_not_move,
+            white_pieces: BitBoard::empty(),
+            black_pieces: BitBoard::empty(),
+            pawns: BitBoard::empty(),
+            bishops: BitBoard::empty(),
+            knights: BitBoard::empty(),
+            rooks: BitBoard::empty(),
+            queens: BitBoard::empty(),
+            kings: BitBoard::empty(),
+            passed_pawns: BitBoard::empty(),
+            castling_rights: 0,
+            short_castling_rook_file: enum_map! { _ => 7 },
+            long_castling_rook_file: enum_map! { _ => 0 },
+            chess960: false,
+            half_move_clock,
+            full_move_number,
+            ep_square,
+            z_hash: ZobristHash::new(),
+            pst_eval: PstEvaluator::new(),
+            move_stack: vec![],
+            null_move_stack: vec![],
+            hash_history: History::new(),
+            is_threefold_repetition: false,
+            is_fivefold_repetition: false
+        };
+        board.init_zobrist_hash();
+        board.init_pst_eval();
 
-            for (i, row) in rows.iter().enumerate() {
-                let rank = i as u8;
-                if row.len() > 8 {
-                    return Err(FenParseError);
+        for (i, row) in rows.iter().enumerate() {
+            let rank = i as u8;
+            let mut file: u8 = 0;
+            for c in row.bytes() {
+                if file >= 8 {
+                    return Err(FenParseError::InvalidField {
+                        field: FenField::PiecePlacement,
+                        reason: format!("rank {} has more than 8 files", rank + 1),
+                    });
                 }
-                let mut file: u8 = 0;
-                for c in row.bytes() {
-                    if b'1' <= c && c <= b'8' {
-                        file += c - b'0';
-                    } else {
-                        if let Ok((piece, side)) = Piece::parse_fen_char(c) {
+                if (b'1'..=b'8').contains(&c) {
+                    file += c - b'0';
+                } else {
+                    match Piece::parse_fen_char(c) {
+                        Ok((piece, side)) => {
                             let square = Square::from_coords(rank, file);
                             board.add_piece(side, piece, square);
-                        } else {
-                            return Err(FenParseError);
                         }
-                        file += 1;
+                        Err(_) => {
+                            return Err(FenParseError::InvalidField {
+                                field: FenField::PiecePlacement,
+                                reason: format!("unexpected character '{}'", c as char),
+                            })
+                        }
                     }
+                    file += 1;
                 }
             }
+            if file != 8 {
+                return Err(FenParseError::InvalidField {
+                    field: FenField::PiecePlacement,
+                    reason: format!("rank {} has {} files", rank + 1, file),
+                });
+            }
+        }
+
+        for c in castling_rights.chars() {
+            board.add_castling_right(c)?;
+        }
+        // the en passant file is only hashed once the pawns that could capture are on the board
+        board.flip_ep_hash();
 
-            board.hash_history.push(board.hash());
-            board.update_passed_pawns();
-            return Ok(board);
+        board.hash_history.push(board.hash());
+        board.update_passed_pawns();
+        Ok(board)
+    }
+
+    // a position without castling rights and en passant square, e.g. for generating endgame tables
+    pub(crate) fn from_pieces(side_to_move: Side, pieces: &[(Side, Piece, Square)]) -> Self {
+        let side_to_move = match side_to_move {
+            Side::White => "w",
+            Side::Black => "b",
+        };
+        let mut board = Board::from_fen_fields("8/8/8/8/8/8/8/8", side_to_move, "-", "-", 0, 1).unwrap();
+        board.hash_history = History::new();
+        for &(side, piece, square) in pieces {
+            board.add_piece(side, piece, square);
+        }
+        board.hash_history.push(board.hash());
+        board.update_passed_pawns();
+        board
+    }
+
+    fn add_castling_right(&mut self, c: char) -> Result<(), FenParseError> {
+        let side = if c.is_ascii_uppercase() { Side::White } else { Side::Black };
+        let back_rank = Board::back_rank(side);
+        let back_rank_pieces = self.get_pieces(side) & BitBoard::rank(back_rank);
+        let king_file = (self.kings & back_rank_pieces).next().map_or(4, |king| king.file());
+        let rook_files = (self.rooks & back_rank_pieces).map(|rook| rook.file());
+        let rook_file = match c.to_ascii_uppercase() {
+            // X-FEN: the outermost rook on the given side of the king
+            'K' => rook_files.filter(|&file| file > king_file).max().unwrap_or(7),
+            'Q' => rook_files.filter(|&file| file < king_file).min().unwrap_or(0),
+            // Shredder-FEN: the file of the castling rook
+            file => {
+                self.chess960 = true;
+                file as u8 - b'A'
+            }
+        };
+        if rook_file == king_file {
+            return Err(FenParseError::InvalidField {
+                field: FenField::CastlingRights,
+                reason: format!("castling rook '{}' is on the king's file", c),
+            });
+        }
+        if king_file != 4 || (rook_file != 0 && rook_file != 7) {
+            self.chess960 = true;
+        }
+        if rook_file > king_file {
+            self.short_castling_rook_file[side] = rook_file;
+            if !self.can_castle_short(side) {
+                self.castling_rights |= Board::short_castling_flag(side);
+                self.z_hash.flip_short_castling(side);
+            }
+        } else {
+            self.long_castling_rook_file[side] = rook_file;
+            if !self.can_castle_long(side) {
+                self.castling_rights |= Board::long_castling_flag(side);
+                self.z_hash.flip_long_castling(side);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn chess960_starting_position(number: u16) -> Option<Self> {
+        if number >= 960 {
+            return None;
+        }
+        // Scharnagl numbering: bishops, queen and knights are placed in turn, then R, K, R fill the gaps
+        let mut back_rank = [None; 8];
+        let mut n = number as usize;
+        back_rank[(n % 4) * 2 + 1] = Some(Piece::Bishop);
+        n /= 4;
+        back_rank[(n % 4) * 2] = Some(Piece::Bishop);
+        n /= 4;
+        let mut place_on_empty_square = |index: usize, piece: Piece| {
+            let file = (0..8).filter(|&f| back_rank[f].is_none()).nth(index).unwrap();
+            back_rank[file] = Some(piece);
+        };
+        place_on_empty_square(n % 6, Piece::Queen);
+        n /= 6;
+        let (first_knight, second_knight) = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)][n];
+        place_on_empty_square(second_knight, Piece::Knight);
+        place_on_empty_square(first_knight, Piece::Knight);
+        for piece in [Piece::Rook, Piece::King, Piece::Rook] {
+            place_on_empty_square(0, piece);
+        }
+        let black_pieces = back_rank.iter().map(|p| p.unwrap().to_fen_char(Side::Black)).collect::<String>();
+        let fen = format!(
+            "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1",
+            black_pieces,
+            black_pieces.to_ascii_uppercase()
+        );
+        let mut board = Board::parse_fen(&fen).unwrap();
+        board.chess960 = true;
+        Some(board)
+    }
+
+    pub fn is_chess960(&self) -> bool {
+        self.chess960
+    }
+
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+    }
+
+    pub fn validate(&self) -> Result<(), IllegalPositionError> {
+        for side in [Side::White, Side::Black] {
+            let pieces = self.get_pieces(side);
+            match (self.kings & pieces).count() {
+                0 => return Err(IllegalPositionError::MissingKing(side)),
+                1 => {}
+                _ => return Err(IllegalPositionError::TooManyKings(side)),
+            }
+            if (self.pawns & pieces).count() > 8 {
+                return Err(IllegalPositionError::TooManyPawns(side));
+            }
+            if pieces.count() > 16 {
+                return Err(IllegalPositionError::TooManyPieces(side));
+            }
+        }
+        if let Some(pawn) = (self.pawns & (BitBoard::rank(0) | BitBoard::rank(7))).next() {
+            return Err(IllegalPositionError::PawnOnBackRank(pawn));
+        }
+        if self.is_side_in_check(self.side_to_not_move) {
+            return Err(IllegalPositionError::OpponentInCheck);
+        }
+        for side in [Side::White, Side::Black] {
+            let pieces = self.get_pieces(side);
+            let king = (self.kings & pieces).single();
+            let has_king_at_home = king.rank() == Board::back_rank(side) && (self.chess960 || king.file() == 4);
+            if self.can_castle_short(side) && !(has_king_at_home && (self.rooks & pieces).is_occupied(self.short_castling_rook(side))) {
+                return Err(IllegalPositionError::InvalidCastlingRights(side));
+            }
+            if self.can_castle_long(side) && !(has_king_at_home && (self.rooks & pieces).is_occupied(self.long_castling_rook(side))) {
+                return Err(IllegalPositionError::InvalidCastlingRights(side));
+            }
+        }
+        if let Some(ep_square) = self.ep_square {
+            // the square must have been skipped by an enemy pawn double push on the previous move
+            let (ep_rank, pawn_delta) = match self.side_to_move {
+                Side::White => (5, -1),
+                Side::Black => (2, 1),
+            };
+            let all_pieces = self.white_pieces | self.black_pieces;
+            let pushed_pawn = ep_square.delta(pawn_delta, 0).unwrap();
+            let pawn_origin = ep_square.delta(-pawn_delta, 0).unwrap();
+            if ep_square.rank() != ep_rank
+                || all_pieces.is_occupied(ep_square)
+                || all_pieces.is_occupied(pawn_origin)
+                || !(self.pawns & self.get_pieces(self.side_to_not_move)).is_occupied(pushed_pawn)
+            {
+                return Err(IllegalPositionError::InvalidEnPassantSquare(ep_square));
+            }
         }
-        Err(FenParseError)
+        Ok(())
+    }
+
+    fn is_side_in_check(&self, side: Side) -> bool {
+        let own_pieces = self.get_pieces(side);
+        let enemy_pieces = self.get_pieces(side.opposite());
+        let all_pieces = own_pieces | enemy_pieces;
+        let king = (self.kings & own_pieces).single();
+        let attackers = (self.pawns & king.bb().pawn_captures(side))
+            | (self.knights & king.knight_moves())
+            | (self.kings & king.king_moves())
+            | ((self.bishops | self.queens) & self.bishop_moves(king, all_pieces))
+            | ((self.rooks | self.queens) & self.rook_moves(king, all_pieces));
+        (attackers & enemy_pieces).any()
     }
 
     fn init_zobrist_hash(&mut self) {
@@ -267,11 +532,19 @@ impl Board {
         if self.can_castle_long(Side::Black) {
             self.z_hash.flip_long_castling(Side::Black);
         }
-        if let Some(ep_square) = self.ep_square {
-            self.z_hash.flip_ep_file(ep_square.file());
+        self.flip_ep_hash();
+        if self.side_to_move == Side::White {
+            self.z_hash.flip_side_to_move();
         }
-        if self.side_to_move == Side::Black {
-            self.z_hash.flip_black_to_move();
+    }
+
+    // Like Polyglot, only hash the en passant file if a pawn of the side to move can capture
+    fn flip_ep_hash(&mut self) {
+        if let Some(ep_square) = self.ep_square {
+            let pawns = self.pawns & self.get_pieces(self.side_to_move);
+            if pawns.pawn_captures(self.side_to_move).is_occupied(ep_square) {
+                self.z_hash.flip_ep_file(ep_square.file());
+            }
         }
     }
 
@@ -318,6 +591,14 @@ impl Board {
         self.z_hash.value
     }
 
+    pub fn half_move_clock(&self) -> u8 {
+        self.half_move_clock
+    }
+
+    pub fn has_castling_rights(&self) -> bool {
+        self.castling_rights != 0
+    }
+
     pub fn full_move_number(&self) -> u16 {
         self.full_move_number
     }
@@ -360,17 +641,13 @@ impl Board {
         if self.castling_rights == 0 {
             sb.append("-");
         } else {
-            if self.can_castle_short(Side::White) {
-                sb.append("K");
-            }
-            if self.can_castle_long(Side::White) {
-                sb.append("Q");
-            }
-            if self.can_castle_short(Side::Black) {
-                sb.append("k");
-            }
-            if self.can_castle_long(Side::Black) {
-                sb.append("q");
+            for side in [Side::White, Side::Black] {
+                if self.can_castle_short(side) {
+                    sb.append(self.castling_right_fen_char(side, self.short_castling_rook(side), 'K'));
+                }
+                if self.can_castle_long(side) {
+                    sb.append(self.castling_right_fen_char(side, self.long_castling_rook(side), 'Q'));
+                }
             }
         }
 
@@ -387,6 +664,24 @@ impl Board {
         sb.string().unwrap()
     }
 
+    fn castling_right_fen_char(&self, side: Side, rook: Square, x_fen_char: char) -> char {
+        // X-FEN only names the rook's file when it isn't the outermost rook on that side of the king
+        let outer_squares = match x_fen_char {
+            'K' => rook.bounding_box(Square::from_coords(rook.rank(), 7)),
+            _ => rook.bounding_box(Square::from_coords(rook.rank(), 0)),
+        };
+        let is_outermost_rook = !(self.rooks & self.get_pieces(side) & outer_squares & !rook).any();
+        let c = if is_outermost_rook {
+            x_fen_char
+        } else {
+            (b'A' + rook.file()) as char
+        };
+        match side {
+            Side::White => c,
+            Side::Black => c.to_ascii_lowercase(),
+        }
+    }
+
     pub fn get_pgn_square_disambiguation(&self, piece: Piece, from: Square, to: Square) -> String {
         let legal_moves = self.legal_moves();
         let potentially_ambiguous_moves = legal_moves
@@ -449,7 +744,12 @@ impl Board {
         (self.kings & own_pieces).piece_value(Piece::King)
     }
 
-    fn get_pieces(&self, side: Side) -> BitBoard {
+    // whether a side has more than king and pawns, when zugzwang is unlikely
+    pub fn has_non_pawn_material(&self, side: Side) -> bool {
+        (self.get_pieces(side) & !(self.pawns | self.kings)).any()
+    }
+
+    pub fn get_pieces(&self, side: Side) -> BitBoard {
         match side {
             Side::White => self.white_pieces,
             Side::Black => self.black_pieces,
@@ -516,43 +816,36 @@ impl Board {
         self.remove_piece(side, piece, from);
         self.add_piece(side, piece, to);
         match piece {
-            Piece::King => {
-                let flag = Board::long_castling_flag(side);
-                if self.castling_rights & flag != 0 {
-                    self.castling_rights &= !flag;
-                    self.z_hash.flip_long_castling(side);
-                }
-                let flag = Board::short_castling_flag(side);
-                if self.castling_rights & flag != 0 {
-                    self.castling_rights &= !flag;
-                    self.z_hash.flip_short_castling(side);
-                }
-            }
-            Piece::Rook => {
-                if from.rank() == Board::back_rank(side) {
-                    match from.file() {
-                        0 => {
-                            let flag = Board::long_castling_flag(side);
-                            if self.castling_rights & flag != 0 {
-                                self.castling_rights &= !flag;
-                                self.z_hash.flip_long_castling(side);
-                            }
-                        }
-                        7 => {
-                            let flag = Board::short_castling_flag(side);
-                            if self.castling_rights & flag != 0 {
-                                self.castling_rights &= !flag;
-                                self.z_hash.flip_short_castling(side);
-                            }
-                        }
-                        _ => {}
-                    };
-                }
-            }
+            Piece::King => self.remove_castling_rights(side),
+            Piece::Rook => self.remove_castling_right_for_rook(side, from),
             _ => {}
         };
     }
 
+    fn remove_castling_rights(&mut self, side: Side) {
+        let flag = Board::long_castling_flag(side);
+        if self.castling_rights & flag != 0 {
+            self.castling_rights &= !flag;
+            self.z_hash.flip_long_castling(side);
+        }
+        let flag = Board::short_castling_flag(side);
+        if self.castling_rights & flag != 0 {
+            self.castling_rights &= !flag;
+            self.z_hash.flip_short_castling(side);
+        }
+    }
+
+    fn remove_castling_right_for_rook(&mut self, side: Side, square: Square) {
+        if self.can_castle_long(side) && square == self.long_castling_rook(side) {
+            self.castling_rights &= !Board::long_castling_flag(side);
+            self.z_hash.flip_long_castling(side);
+        }
+        if self.can_castle_short(side) && square == self.short_castling_rook(side) {
+            self.castling_rights &= !Board::short_castling_flag(side);
+            self.z_hash.flip_short_castling(side);
+        }
+    }
+
     fn undo_move(&mut self, side: Side, piece: Piece, from: Square, to: Square) {
         self.remove_piece(side, piece, to);
         self.add_piece(side, piece, from);
@@ -561,25 +854,7 @@ impl Board {
     fn apply_capture(&mut self, side: Side, piece: Piece, square: Square) {
         self.remove_piece(side, piece, square);
         if piece == Piece::Rook {
-            if square.rank() == Board::back_rank(side) {
-                match square.file() {
-                    0 => {
-                        let flag = Board::long_castling_flag(side);
-                        if self.castling_rights & flag == flag {
-                            self.castling_rights &= !flag;
-                            self.z_hash.flip_long_castling(side);
-                        }
-                    }
-                    7 => {
-                        let flag = Board::short_castling_flag(side);
-                        if self.castling_rights & flag == flag {
-                            self.castling_rights &= !flag;
-                            self.z_hash.flip_short_castling(side);
-                        }
-                    }
-                    _ => {}
-                };
-            }
+            self.remove_castling_right_for_rook(side, square);
         }
     }
 
@@ -598,8 +873,12 @@ impl Board {
     }
 
     pub fn get_uci_move(&self, uci_move: &str) -> Result<Move, IllegalMoveError> {
+        // king-takes-rook castling is always accepted; king-to-destination castling only outside Chess960,
+        // where it could be confused with a regular king move
         let legal_moves = self.legal_moves();
-        let selected_move = legal_moves.iter().find(|&m| m.to_uci_string() == uci_move);
+        let selected_move = legal_moves.iter().find(|&m| {
+            m.to_uci_string_chess960() == uci_move || (!self.chess960 && m.to_uci_string() == uci_move)
+        });
         match selected_move {
             Some(&move_) => Ok(move_),
             None => Err(IllegalMoveError),
@@ -607,14 +886,77 @@ impl Board {
     }
 
     pub fn push_uci(&mut self, uci_move: &str) -> Result<(), IllegalMoveError> {
+        let move_ = self.get_uci_move(uci_move)?;
+        self.push(&move_);
+        Ok(())
+    }
+
+    pub fn parse_san(&self, san: &str) -> Result<Move, SanParseError> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(
+                r"^([NBRQK])?([a-h])?([1-8])?(x)?([a-h][1-8])(?:=?([NBRQ]))?$"
+            )
+            .unwrap();
+        }
+        let spec = san.trim_end_matches(['!', '?']).trim_end_matches(['+', '#']);
         let legal_moves = self.legal_moves();
-        let selected_move = legal_moves.iter().find(|&m| m.to_uci_string() == uci_move);
-        match selected_move {
-            Some(move_) => Ok(self.push(move_)),
-            None => Err(IllegalMoveError),
+
+        let candidates: Vec<Move> = match spec {
+            "O-O" | "0-0" => legal_moves
+                .into_iter()
+                .filter(|m| matches!(m, ShortCastling(_)))
+                .collect(),
+            "O-O-O" | "0-0-0" => legal_moves
+                .into_iter()
+                .filter(|m| matches!(m, LongCastling(_)))
+                .collect(),
+            _ => {
+                let captures = match RE.captures(spec) {
+                    Some(c) => c,
+                    None => return Err(SanParseError::InvalidSyntax(san.to_string())),
+                };
+                let piece = match captures.get(1) {
+                    Some(p) => Piece::parse_pgn_char(p.as_str().as_bytes()[0]).unwrap(),
+                    None => Piece::Pawn,
+                };
+                let from_file = captures.get(2).map(|f| f.as_str().as_bytes()[0] - b'a');
+                let from_rank = captures.get(3).map(|r| r.as_str().as_bytes()[0] - b'1');
+                let is_capture = captures.get(4).is_some();
+                let to: Square = captures[5].parse().unwrap();
+                let promotion_piece = captures
+                    .get(6)
+                    .and_then(|p| Piece::parse_pgn_char(p.as_str().as_bytes()[0]));
+                if piece != Piece::Pawn && promotion_piece.is_some() {
+                    return Err(SanParseError::InvalidSyntax(san.to_string()));
+                }
+                legal_moves
+                    .into_iter()
+                    .filter(|m| {
+                        !matches!(m, ShortCastling(_) | LongCastling(_))
+                            && m.piece() == piece
+                            && m.to() == to
+                            && from_file.is_none_or(|f| m.from().file() == f)
+                            && from_rank.is_none_or(|r| m.from().rank() == r)
+                            && m.is_capture() == is_capture
+                            && (promotion_piece.is_none() || m.promotion_piece() == promotion_piece)
+                    })
+                    .collect()
+            }
+        };
+
+        match candidates.len() {
+            0 => Err(SanParseError::IllegalMove(san.to_string())),
+            1 => Ok(candidates[0]),
+            _ => Err(SanParseError::AmbiguousMove(san.to_string())),
         }
     }
 
+    pub fn push_san(&mut self, san: &str) -> Result<Move, SanParseError> {
+        let move_ = self.parse_san(san)?;
+        self.push(&move_);
+        Ok(move_)
+    }
+
     pub fn side_to_move_has_passed_pawns(&self) -> bool {
         let passed_pawns = match self.side_to_move {
             Side::White => self.white_pieces,
@@ -652,10 +994,8 @@ impl Board {
 
     pub fn push(&mut self, move_: &Move) {
         let move_undo_info =
-            MoveUndoInfo::new(self.castling_rights, self.half_move_clock, self.ep_square, self.is_threefold_repetition, self.passed_pawns);
-        if let Some(ep_square) = self.ep_square {
-            self.z_hash.flip_ep_file(ep_square.file());
-        }
+            MoveUndoInfo::new(self.castling_rights, self.half_move_clock, self.ep_square, self.is_threefold_repetition, self.is_fivefold_repetition, self.passed_pawns);
+        self.flip_ep_hash();
         self.ep_square = None;
         match move_ {
             Regular(m) => {
@@ -666,12 +1006,8 @@ impl Board {
                     self.half_move_clock += 1;
                 }
             }
-            ShortCastling(_side) => {
-                self.apply_short_castling_move();
-                self.half_move_clock += 1;
-            }
-            LongCastling(_side) => {
-                self.apply_long_castling_move();
+            ShortCastling(m) | LongCastling(m) => {
+                self.apply_castling_move(m);
                 self.half_move_clock += 1;
             }
             EnPassantCapture(m) => {
@@ -686,9 +1022,10 @@ impl Board {
         if move_.is_pawn_involved() {
             self.update_passed_pawns();
         }
-        self.move_stack.push((move_.clone(), move_undo_info));
+        self.move_stack.push((*move_, move_undo_info));
         std::mem::swap(&mut self.side_to_move, &mut self.side_to_not_move);
-        self.z_hash.flip_black_to_move();
+        self.z_hash.flip_side_to_move();
+        self.flip_ep_hash();
         if self.side_to_move == Side::White {
             self.full_move_number += 1;
         }
@@ -696,15 +1033,18 @@ impl Board {
         if hash_count >= 3 {
             self.is_threefold_repetition = true;
         }
+        if hash_count >= 5 {
+            self.is_fivefold_repetition = true;
+        }
     }
 
     pub fn pop(&mut self) {
         if let Some((move_, move_undo_info)) = self.move_stack.pop() {
             self.hash_history.pop(self.hash());
+            self.flip_ep_hash();
             match move_ {
                 Regular(m) => self.undo_regular_move(&m),
-                ShortCastling(_side) => self.undo_short_castling_move(),
-                LongCastling(_side) => self.undo_long_castling_move(),
+                ShortCastling(m) | LongCastling(m) => self.undo_castling_move(&m),
                 EnPassantCapture(m) => self.undo_ep_capture_move(&m),
                 Promotion(m) => self.undo_promotion_move(&m)
             };
@@ -724,24 +1064,49 @@ impl Board {
                 }
                 self.castling_rights = move_undo_info.castling_rights;
             }
-            if let Some(ep_square) = self.ep_square {
-                self.z_hash.flip_ep_file(ep_square.file());
-            }
             self.ep_square = move_undo_info.ep_square;
-            if let Some(ep_square) = self.ep_square {
-                self.z_hash.flip_ep_file(ep_square.file());
-            }
             self.half_move_clock = move_undo_info.half_move_clock;
             std::mem::swap(&mut self.side_to_move, &mut self.side_to_not_move);
-            self.z_hash.flip_black_to_move();
+            self.z_hash.flip_side_to_move();
+            self.flip_ep_hash();
             if self.side_to_move == Side::Black {
                 self.full_move_number -= 1;
             }
             self.is_threefold_repetition = move_undo_info.is_threefold_repetition;
+            self.is_fivefold_repetition = move_undo_info.is_fivefold_repetition;
             self.passed_pawns = move_undo_info.passed_pawns;
         }
     }
 
+    // passes the turn without moving, e.g. for null move pruning; it must be undone with pop_null
+    // before any move pushed earlier is popped
+    pub fn push_null(&mut self) {
+        let move_undo_info =
+            MoveUndoInfo::new(self.castling_rights, self.half_move_clock, self.ep_square, self.is_threefold_repetition, self.is_fivefold_repetition, self.passed_pawns);
+        self.flip_ep_hash();
+        self.ep_square = None;
+        self.half_move_clock += 1;
+        self.null_move_stack.push(move_undo_info);
+        std::mem::swap(&mut self.side_to_move, &mut self.side_to_not_move);
+        self.z_hash.flip_side_to_move();
+        if self.side_to_move == Side::White {
+            self.full_move_number += 1;
+        }
+    }
+
+    pub fn pop_null(&mut self) {
+        if let Some(move_undo_info) = self.null_move_stack.pop() {
+            std::mem::swap(&mut self.side_to_move, &mut self.side_to_not_move);
+            self.z_hash.flip_side_to_move();
+            if self.side_to_move == Side::Black {
+                self.full_move_number -= 1;
+            }
+            self.ep_square = move_undo_info.ep_square;
+            self.half_move_clock = move_undo_info.half_move_clock;
+            self.flip_ep_hash();
+        }
+    }
+
     fn apply_regular_move(&mut self, m: &RegularMove) {
         if let Some(captured_piece) = m.captured_piece() {
             self.apply_capture(self.side_to_not_move, captured_piece, m.to());
@@ -749,12 +1114,10 @@ impl Board {
             if self.side_to_move == Side::White {
                 if m.from().rank() == 1 && m.to().rank() == 3 {
                     self.ep_square = m.from().delta(1, 0);
-                    self.z_hash.flip_ep_file(m.to().file());
                 }
             } else {
                 if m.from().rank() == 6 && m.to().rank() == 4 {
                     self.ep_square = m.from().delta(-1, 0);
-                    self.z_hash.flip_ep_file(m.to().file());
                 }
             }
         }
@@ -768,40 +1131,20 @@ impl Board {
         }
     }
 
-    fn apply_short_castling_move(&mut self) {
-        let (king, king_to, rook, rook_to) = match self.side_to_move {
-            Side::White => (sq!(e1), sq!(g1), sq!(h1), sq!(f1)),
-            Side::Black => (sq!(e8), sq!(g8), sq!(h8), sq!(f8)),
-        };
-        self.apply_move(self.side_to_move, Piece::King, king, king_to);
-        self.apply_move(self.side_to_move, Piece::Rook, rook, rook_to);
+    fn apply_castling_move(&mut self, m: &CastlingMove) {
+        // remove both pieces before placing them, as the king may land on the rook's square or vice versa
+        self.remove_piece(self.side_to_move, Piece::King, m.king_from());
+        self.remove_piece(self.side_to_move, Piece::Rook, m.rook_from());
+        self.add_piece(self.side_to_move, Piece::King, m.king_to());
+        self.add_piece(self.side_to_move, Piece::Rook, m.rook_to());
+        self.remove_castling_rights(self.side_to_move);
     }
 
-    fn undo_short_castling_move(&mut self) {
-        let (king, king_to, rook, rook_to) = match self.side_to_not_move {
-            Side::White => (sq!(e1), sq!(g1), sq!(h1), sq!(f1)),
-            Side::Black => (sq!(e8), sq!(g8), sq!(h8), sq!(f8)),
-        };
-        self.undo_move(self.side_to_not_move, Piece::King, king, king_to);
-        self.undo_move(self.side_to_not_move, Piece::Rook, rook, rook_to);
-    }
-
-    fn apply_long_castling_move(&mut self) {
-        let (king, king_to, rook, rook_to) = match self.side_to_move {
-            Side::White => (sq!(e1), sq!(c1), sq!(a1), sq!(d1)),
-            Side::Black => (sq!(e8), sq!(c8), sq!(a8), sq!(d8)),
-        };
-        self.apply_move(self.side_to_move, Piece::King, king, king_to);
-        self.apply_move(self.side_to_move, Piece::Rook, rook, rook_to);
-    }
-
-    fn undo_long_castling_move(&mut self) {
-        let (king, king_to, rook, rook_to) = match self.side_to_not_move {
-            Side::White => (sq!(e1), sq!(c1), sq!(a1), sq!(d1)),
-            Side::Black => (sq!(e8), sq!(c8), sq!(a8), sq!(d8)),
-        };
-        self.undo_move(self.side_to_not_move, Piece::King, king, king_to);
-        self.undo_move(self.side_to_not_move, Piece::Rook, rook, rook_to);
+    fn undo_castling_move(&mut self, m: &CastlingMove) {
+        self.remove_piece(self.side_to_not_move, Piece::King, m.king_to());
+        self.remove_piece(self.side_to_not_move, Piece::Rook, m.rook_to());
+        self.add_piece(self.side_to_not_move, Piece::King, m.king_from());
+        self.add_piece(self.side_to_not_move, Piece::Rook, m.rook_from());
     }
 
     fn apply_ep_capture_move(&mut self, m: &EnPassantCaptureMove) {
@@ -838,6 +1181,14 @@ impl Board {
         self.castling_rights & flag == flag
     }
 
+    fn short_castling_rook(&self, side: Side) -> Square {
+        Square::from_coords(Board::back_rank(side), self.short_castling_rook_file[side])
+    }
+
+    fn long_castling_rook(&self, side: Side) -> Square {
+        Square::from_coords(Board::back_rank(side), self.long_castling_rook_file[side])
+    }
+
     pub fn side_to_move(&self) -> Side {
         self.side_to_move
     }
@@ -854,6 +1205,53 @@ impl Board {
         self.half_move_clock >= 100
     }
 
+    pub fn is_draw_by_fivefold_repetition(&self) -> bool {
+        self.is_fivefold_repetition
+    }
+
+    pub fn is_draw_by_seventy_five_move_rule(&self) -> bool {
+        self.half_move_clock >= 150
+    }
+
+    pub fn is_insufficient_material(&self) -> bool {
+        if (self.pawns | self.rooks | self.queens).any() {
+            return false;
+        }
+        if (self.knights | self.bishops).count() <= 1 {
+            // KvK, KNvK, KBvK
+            return true;
+        }
+        if self.knights.any() {
+            return false;
+        }
+        // any number of bishops, provided they are all on squares of the same colour
+        let light_squares = BitBoard::new(LIGHT_SQUARES);
+        !(self.bishops & light_squares).any() || !(self.bishops & !light_squares).any()
+    }
+
+    pub fn outcome(&self) -> Option<Outcome> {
+        if self.legal_moves().is_empty() {
+            return if self.is_in_check() {
+                Some(Outcome::Checkmate { winner: self.side_to_not_move })
+            } else {
+                Some(Outcome::Stalemate)
+            };
+        }
+        if self.is_insufficient_material() {
+            Some(Outcome::InsufficientMaterial)
+        } else if self.is_draw_by_fivefold_repetition() {
+            Some(Outcome::FivefoldRepetition)
+        } else if self.is_draw_by_seventy_five_move_rule() {
+            Some(Outcome::SeventyFiveMoveRule)
+        } else if self.is_draw_by_threefold_repetition() {
+            Some(Outcome::ThreefoldRepetition)
+        } else if self.is_draw_by_fifty_move_rule() {
+            Some(Outcome::FiftyMoveRule)
+        } else {
+            None
+        }
+    }
+
     pub fn is_in_check(&self) -> bool {
         let all_pieces = self.white_pieces | self.black_pieces;
         let (own_pieces, enemy_pieces) = match self.side_to_move {
@@ -1041,22 +1439,17 @@ impl Board {
 
         if !in_check {
             // castling
-            let danger_squares = all_pieces | attacked_squares;
             if self.can_castle_short(self.side_to_move) {
-                let king_to_square = own_king.delta(0, 2).unwrap();
-                let castling_path = own_king.bounding_box(king_to_square) & !own_king;
-                if !(castling_path & danger_squares).any() {
-                    moves.push(Move::short_castling(self.side_to_move))
+                let castling_move = CastlingMove::new(own_king, self.short_castling_rook(self.side_to_move));
+                if self.is_castling_legal(&castling_move, own_pieces, enemy_pieces, attacked_squares) {
+                    moves.push(ShortCastling(castling_move))
                 }
             }
 
             if self.can_castle_long(self.side_to_move) {
-                let king_to_square = own_king.delta(0, -2).unwrap();
-                let extra_square = own_king.delta(0, -3).unwrap();
-                let castling_path = own_king.bounding_box(king_to_square) & !own_king;
-                if !(castling_path & danger_squares).any() && !all_pieces.is_occupied(extra_square)
-                {
-                    moves.push(Move::long_castling(self.side_to_move))
+                let castling_move = CastlingMove::new(own_king, self.long_castling_rook(self.side_to_move));
+                if self.is_castling_legal(&castling_move, own_pieces, enemy_pieces, attacked_squares) {
+                    moves.push(LongCastling(castling_move))
                 }
             }
         }
@@ -1272,6 +1665,61 @@ impl Board {
         moves.len()
     }
 
+    // whether the move can be made apart from leaving the own king in check, e.g. a move unpacked
+    // from a transposition table entry that may belong to another position
+    pub fn is_pseudo_legal(&self, move_: &Move) -> bool {
+        let own_pieces = self.get_pieces(self.side_to_move);
+        let enemy_pieces = self.get_pieces(self.side_to_not_move);
+        let all_pieces = own_pieces | enemy_pieces;
+        let (from, to) = (move_.from(), move_.to());
+        match move_ {
+            ShortCastling(m) | LongCastling(m) => {
+                let (can_castle, rook) = match move_ {
+                    ShortCastling(_) => (self.can_castle_short(self.side_to_move), self.short_castling_rook(self.side_to_move)),
+                    _ => (self.can_castle_long(self.side_to_move), self.long_castling_rook(self.side_to_move))
+                };
+                if !can_castle || m.rook_from() != rook || !(self.kings & own_pieces).is_occupied(from) {
+                    return false;
+                }
+                let attacked_squares = self.attacked_squares(own_pieces, enemy_pieces, all_pieces);
+                return self.is_castling_legal(m, own_pieces, enemy_pieces, attacked_squares);
+            },
+            EnPassantCapture(m) => {
+                return self.ep_square == Some(to)
+                    && (self.pawns & own_pieces).is_occupied(from)
+                    && from.bb().pawn_captures(self.side_to_move).is_occupied(to)
+                    && to.bb().pawn_pushes(self.side_to_not_move).is_occupied(m.captured_pawn());
+            },
+            _ => {}
+        }
+        if self.get_piece(self.side_to_move, from) != Some(move_.piece())
+            || self.get_piece(self.side_to_not_move, to) != move_.captured_piece()
+            || own_pieces.is_occupied(to) {
+            return false;
+        }
+        let targets = match move_.piece() {
+            Piece::Pawn => {
+                // pawns reaching the back rank must promote, and only they can
+                if (to.rank() == Board::back_rank(self.side_to_not_move)) != matches!(move_, Promotion(_)) {
+                    return false;
+                }
+                if move_.is_capture() {
+                    from.bb().pawn_captures(self.side_to_move)
+                } else {
+                    let pushes = from.bb().pawn_pushes(self.side_to_move) & !all_pieces;
+                    let double_push_rank = BitBoard::rank(Board::pawn_double_push_rank(self.side_to_move));
+                    pushes | (pushes.pawn_pushes(self.side_to_move) & double_push_rank & !all_pieces)
+                }
+            },
+            Piece::Knight => from.knight_moves(),
+            Piece::Bishop => self.bishop_moves(from, all_pieces),
+            Piece::Rook => self.rook_moves(from, all_pieces),
+            Piece::Queen => self.bishop_moves(from, all_pieces) | self.rook_moves(from, all_pieces),
+            Piece::King => from.king_moves(),
+        };
+        targets.is_occupied(to)
+    }
+
     fn bishop_moves(&self, square: Square, all_pieces: BitBoard) -> BitBoard {
         lazy_static! {
             static ref BISHOP_MAGICS: Vec<MagicBitBoardTable> = build_magic_bishop_tables();
@@ -1292,6 +1740,28 @@ impl Board {
         }
     }
 
+    fn is_castling_legal(
+        &self,
+        m: &CastlingMove,
+        own_pieces: BitBoard,
+        enemy_pieces: BitBoard,
+        attacked_squares: BitBoard,
+    ) -> bool {
+        if m.king_from().rank() != Board::back_rank(self.side_to_move) || !(self.rooks & own_pieces).is_occupied(m.rook_from()) {
+            return false;
+        }
+        // every square either piece passes over must be empty, apart from the king and castling rook themselves
+        let other_pieces = (own_pieces | enemy_pieces) & !m.king_from() & !m.rook_from();
+        let king_path = m.king_from().bounding_box(m.king_to());
+        let rook_path = m.rook_from().bounding_box(m.rook_to());
+        if ((king_path | rook_path) & other_pieces).any() || (king_path & attacked_squares).any() {
+            return false;
+        }
+        // in Chess960 the castling rook may have been shielding the king's destination along the back rank
+        let enemy_rooks_and_queens = (self.rooks | self.queens) & enemy_pieces;
+        !(self.rook_moves(m.king_to(), other_pieces | m.rook_to()) & enemy_rooks_and_queens).any()
+    }
+
     fn attacked_squares(
         &self,
         own_pieces: BitBoard,
@@ -1381,6 +1851,7 @@ impl Board {
 mod tests {
     use super::*;
     use crate::perft::perft;
+    use crate::PackedMove;
     use test_case::test_case;
 
     const POSITION_1: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
@@ -1393,6 +1864,12 @@ mod tests {
     const POSITION_7: &str = "rn1qk1nr/pbppp1bp/1p4p1/4Pp2/3K4/8/PPPP1PPP/RNBQ1BNR w kq f6 0 1";
     const POSITION_8: &str = "rnb1k1nr/pppp1ppp/8/4p3/1b1P3q/2Q5/PPP1PPPP/RNB1KBNR w KQkq - 0 4";
 
+    const CHESS960_POSITION_1: &str = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+    const CHESS960_POSITION_2: &str = "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9";
+    const CHESS960_POSITION_3: &str = "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9";
+    const CHESS960_POSITION_4: &str = "1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9";
+    const CHESS960_POSITION_5: &str = "qnbnr1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w HEhe - 1 9";
+
     #[test]
     fn test_legal_moves_starting_position() {
         let board = Board::starting_position();
@@ -1421,6 +1898,52 @@ mod tests {
         assert_eq!(output_fen.as_str(), input_fen);
     }
 
+    #[test_case(POSITION_1 ; "position 1")]
+    #[test_case(POSITION_2 ; "position 2")]
+    #[test_case(POSITION_3 ; "position 3")]
+    #[test_case(POSITION_4 ; "position 4")]
+    #[test_case(POSITION_5 ; "position 5")]
+    #[test_case(POSITION_6 ; "position 6")]
+    #[test_case(POSITION_7 ; "position 7")]
+    #[test_case(POSITION_8 ; "position 8")]
+    fn test_parse_fen_strict(input_fen: &str) {
+        assert!(Board::parse_fen_strict(input_fen).is_ok());
+    }
+
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0", FenParseError::MissingField(FenField::FullMoveNumber) ; "missing clock")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 moves e2e4", FenParseError::TooManyFields(8) ; "trailing moves")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenParseError::InvalidField { field: FenField::PiecePlacement, reason: "expected 8 ranks, found 7".to_string() } ; "seven ranks")]
+    #[test_case("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenParseError::InvalidField { field: FenField::PiecePlacement, reason: "unexpected character '9'".to_string() } ; "bad digit")]
+    #[test_case("rnbqkbnr/ppppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenParseError::InvalidField { field: FenField::PiecePlacement, reason: "rank 7 has more than 8 files".to_string() } ; "long rank")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/7/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenParseError::InvalidField { field: FenField::PiecePlacement, reason: "rank 3 has 7 files".to_string() } ; "short rank")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1", FenParseError::InvalidField { field: FenField::SideToMove, reason: "expected 'w' or 'b', found 'x'".to_string() } ; "bad side to move")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQxq - 0 1", FenParseError::InvalidField { field: FenField::CastlingRights, reason: "unexpected character 'x'".to_string() } ; "bad castling rights")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e4 0 1", FenParseError::InvalidField { field: FenField::EnPassantSquare, reason: "'e4' is not a square on the third or sixth rank".to_string() } ; "bad en passant square")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1", FenParseError::InvalidField { field: FenField::HalfMoveClock, reason: "'x' is not a number between 0 and 255".to_string() } ; "bad half move clock")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 -1", FenParseError::InvalidField { field: FenField::FullMoveNumber, reason: "'-1' is not a number between 0 and 65535".to_string() } ; "bad full move number")]
+    fn test_parse_fen_errors(input_fen: &str, expected_error: FenParseError) {
+        assert_eq!(Board::parse_fen(input_fen).unwrap_err(), expected_error);
+    }
+
+    #[test_case("rnbq1bnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ - 0 1", IllegalPositionError::MissingKing(Side::Black) ; "missing king")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBKKBNR w kq - 0 1", IllegalPositionError::TooManyKings(Side::White) ; "two kings")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/P7/PPPPPPPP/RNBQKBNR w KQkq - 0 1", IllegalPositionError::TooManyPawns(Side::White) ; "nine pawns")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/N7/PPPPPPPP/RNBQKBNR w KQkq - 0 1", IllegalPositionError::TooManyPieces(Side::White) ; "seventeen pieces")]
+    #[test_case("4k2P/8/8/8/8/8/8/4K3 w - - 0 1", IllegalPositionError::PawnOnBackRank(sq!(h8)) ; "pawn on eighth rank")]
+    #[test_case("4k3/8/8/8/8/8/8/p3K3 w - - 0 1", IllegalPositionError::PawnOnBackRank(sq!(a1)) ; "pawn on first rank")]
+    #[test_case("4k2R/8/8/8/8/8/8/4K3 w - - 0 1", IllegalPositionError::OpponentInCheck ; "black in check with white to move")]
+    #[test_case("8/8/8/8/8/8/3k4/4K3 b - - 0 1", IllegalPositionError::OpponentInCheck ; "adjacent kings")]
+    #[test_case("4k3/8/8/8/8/8/3p4/4K3 b - - 0 1", IllegalPositionError::OpponentInCheck ; "white in check from pawn")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN1 w KQkq - 0 1", IllegalPositionError::InvalidCastlingRights(Side::White) ; "castling without rook")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1BNR w KQkq - 0 1", IllegalPositionError::MissingKing(Side::White) ; "castling without king")]
+    #[test_case("rnbq1bnr/ppppkppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", IllegalPositionError::InvalidCastlingRights(Side::Black) ; "castling after king move")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e6 0 1", IllegalPositionError::InvalidEnPassantSquare(sq!(e6)) ; "en passant without pawn")]
+    #[test_case("rnbqkbnr/pppp1ppp/8/4p3/8/8/PPPPPPPP/RNBQKBNR w KQkq e3 0 1", IllegalPositionError::InvalidEnPassantSquare(sq!(e3)) ; "en passant on wrong side")]
+    fn test_parse_fen_strict_errors(input_fen: &str, expected_error: IllegalPositionError) {
+        assert!(Board::parse_fen(input_fen).is_ok());
+        assert_eq!(Board::parse_fen_strict(input_fen).unwrap_err(), FenParseError::IllegalPosition(expected_error));
+    }
+
     #[test_case(POSITION_1, 20 ; "position 1")]
     #[test_case(POSITION_2, 48 ; "position 2")]
     #[test_case(POSITION_3, 14 ; "position 3")]
@@ -1477,6 +2000,117 @@ mod tests {
         assert_eq!(result, expected_result);
     }
 
+    #[test_case(CHESS960_POSITION_1, 1, 21 ; "chess960 position 1, depth 1")]
+    #[test_case(CHESS960_POSITION_1, 2, 528 ; "chess960 position 1, depth 2")]
+    #[test_case(CHESS960_POSITION_1, 3, 12_189 ; "chess960 position 1, depth 3")]
+    #[test_case(CHESS960_POSITION_1, 4, 326_672 ; "chess960 position 1, depth 4")]
+    #[test_case(CHESS960_POSITION_2, 1, 20 ; "chess960 position 2, depth 1")]
+    #[test_case(CHESS960_POSITION_2, 2, 479 ; "chess960 position 2, depth 2")]
+    #[test_case(CHESS960_POSITION_2, 3, 10_471 ; "chess960 position 2, depth 3")]
+    #[test_case(CHESS960_POSITION_2, 4, 273_318 ; "chess960 position 2, depth 4")]
+    #[test_case(CHESS960_POSITION_3, 1, 22 ; "chess960 position 3, depth 1")]
+    #[test_case(CHESS960_POSITION_3, 2, 593 ; "chess960 position 3, depth 2")]
+    #[test_case(CHESS960_POSITION_3, 3, 13_440 ; "chess960 position 3, depth 3")]
+    #[test_case(CHESS960_POSITION_3, 4, 382_958 ; "chess960 position 3, depth 4")]
+    #[test_case(CHESS960_POSITION_4, 1, 28 ; "chess960 position 4, depth 1")]
+    #[test_case(CHESS960_POSITION_4, 2, 1_120 ; "chess960 position 4, depth 2")]
+    #[test_case(CHESS960_POSITION_4, 3, 31_058 ; "chess960 position 4, depth 3")]
+    #[test_case(CHESS960_POSITION_4, 4, 1_171_749 ; "chess960 position 4, depth 4")]
+    #[test_case(CHESS960_POSITION_5, 1, 29 ; "chess960 position 5, depth 1")]
+    #[test_case(CHESS960_POSITION_5, 2, 899 ; "chess960 position 5, depth 2")]
+    #[test_case(CHESS960_POSITION_5, 3, 26_578 ; "chess960 position 5, depth 3")]
+    #[test_case(CHESS960_POSITION_5, 4, 824_055 ; "chess960 position 5, depth 4")]
+    fn test_chess960_perft(input_fen: &str, depth: u8, expected_result: u64) {
+        let mut board = Board::parse_fen(input_fen).unwrap();
+        assert!(board.is_chess960());
+        let fen_before = board.to_fen_string();
+        let result = perft(&mut board, depth);
+        assert_eq!(board.to_fen_string(), fen_before);
+        assert_eq!(result, expected_result);
+    }
+
+    #[test_case(CHESS960_POSITION_1, "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9" ; "shredder fen")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1", POSITION_1 ; "shredder fen for standard position")]
+    #[test_case("k7/8/8/8/8/8/8/3KR2R w E - 0 1", "k7/8/8/8/8/8/8/3KR2R w E - 0 1" ; "inner rook")]
+    #[test_case("k7/8/8/8/8/8/8/3KR2R w K - 0 1", "k7/8/8/8/8/8/8/3KR2R w K - 0 1" ; "outer rook")]
+    #[test_case("k7/8/8/8/8/8/8/3KR2R w H - 0 1", "k7/8/8/8/8/8/8/3KR2R w K - 0 1" ; "outer rook by file")]
+    fn test_chess960_fen(input_fen: &str, expected_fen: &str) {
+        let board = Board::parse_fen_strict(input_fen).unwrap();
+        assert!(board.is_chess960());
+        assert_eq!(board.to_fen_string(), expected_fen);
+    }
+
+    #[test_case("4k3/8/8/8/8/8/8/R5KR w HA - 0 1", "g1h1", "4k3/8/8/8/8/8/8/R4RK1 b - - 1 1" ; "short castling without moving the king")]
+    #[test_case("4k3/8/8/8/8/8/8/R5KR w HA - 0 1", "g1a1", "4k3/8/8/8/8/8/8/2KR3R b - - 1 1" ; "long castling")]
+    #[test_case("4k3/8/8/8/8/8/8/RK6 w A - 0 1", "b1a1", "4k3/8/8/8/8/8/8/2KR4 b - - 1 1" ; "king and rook swap sides")]
+    #[test_case("1r2k3/8/8/8/8/8/8/1R1K4 w Bb - 0 1", "d1b1", "1r2k3/8/8/8/8/8/8/2KR4 b q - 1 1" ; "long castling onto attacked rook square")]
+    fn test_chess960_castling(input_fen: &str, uci_move: &str, expected_fen: &str) {
+        let mut board = Board::parse_fen(input_fen).unwrap();
+        let hash_before = board.hash();
+        let move_ = board.get_uci_move(uci_move).unwrap();
+        assert_eq!(move_.to_uci_string_chess960(), uci_move);
+        board.push(&move_);
+        assert_eq!(board.to_fen_string(), expected_fen);
+        assert_eq!(board.hash(), Board::parse_fen(expected_fen).unwrap().hash());
+        board.pop();
+        assert_eq!(board.to_fen_string(), Board::parse_fen(input_fen).unwrap().to_fen_string());
+        assert_eq!(board.hash(), hash_before);
+    }
+
+    #[test]
+    fn test_chess960_castling_rook_shields_king_destination() {
+        let board = Board::parse_fen("4k3/8/8/8/8/8/8/1R1K4 w B - 0 1").unwrap();
+        assert!(board.get_uci_move("d1b1").is_ok());
+        let board = Board::parse_fen("4k3/8/8/8/8/8/8/qR1K4 w B - 0 1").unwrap();
+        assert!(board.get_uci_move("d1b1").is_err());
+    }
+
+    #[test]
+    fn test_castling_uci_encoding() {
+        let board = Board::parse_fen(POSITION_2).unwrap();
+        assert_eq!(board.get_uci_move("e1g1").unwrap(), board.get_uci_move("e1h1").unwrap());
+        assert_eq!(board.get_uci_move("e1g1").unwrap().to_uci_string_chess960(), "e1h1");
+        let board = Board::parse_fen("4k3/8/8/8/8/8/8/5K1R w H - 0 1").unwrap();
+        // f1g1 is a regular king move in Chess960, so castling must be given as king-takes-rook
+        assert!(matches!(board.get_uci_move("f1h1"), Ok(ShortCastling(_))));
+        assert!(matches!(board.get_uci_move("f1g1"), Ok(Regular(_))));
+    }
+
+    #[test_case(POSITION_2 ; "position 2")]
+    #[test_case(POSITION_3 ; "position 3")]
+    #[test_case(POSITION_4 ; "position 4")]
+    #[test_case(POSITION_5 ; "position 5")]
+    #[test_case(POSITION_7 ; "position 7")]
+    #[test_case("1r2k2r/8/8/8/3pP3/8/8/RK5R b Kkq e3 0 1" ; "chess960 castling and en passant")]
+    fn test_packed_moves(input_fen: &str) {
+        let board = Board::parse_fen(input_fen).unwrap();
+        let legal_moves = board.legal_moves();
+        for move_ in legal_moves.iter() {
+            assert_eq!(PackedMove::pack(move_).unpack(&board).as_ref(), Some(move_));
+            assert!(board.is_pseudo_legal(move_));
+        }
+        // the other pseudo-legal moves all leave the own king in check
+        for raw_move in 0..=u16::MAX {
+            let Some(move_) = PackedMove(raw_move).unpack(&board) else { continue };
+            if !board.is_pseudo_legal(&move_) {
+                assert!(!legal_moves.contains(&move_));
+                continue;
+            }
+            let mut board_clone = board.clone();
+            board_clone.push(&move_);
+            assert_ne!(legal_moves.contains(&move_), board_clone.is_side_in_check(board.side_to_move()), "{}", move_.to_uci_string());
+        }
+    }
+
+    #[test_case(0, "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1" ; "first position")]
+    #[test_case(518, POSITION_1 ; "standard position")]
+    #[test_case(959, "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w KQkq - 0 1" ; "last position")]
+    fn test_chess960_starting_position(number: u16, expected_fen: &str) {
+        let board = Board::chess960_starting_position(number).unwrap();
+        assert!(board.is_chess960());
+        assert_eq!(board.to_fen_string(), expected_fen);
+    }
+
     #[test_case(POSITION_1 ; "position 1")]
     #[test_case(POSITION_2 ; "position 2")]
     #[test_case(POSITION_3 ; "position 3")]
@@ -1496,6 +2130,174 @@ mod tests {
         }
     }
 
+    #[test_case(POSITION_1 ; "position 1")]
+    #[test_case(POSITION_2 ; "position 2")]
+    #[test_case(POSITION_3 ; "position 3")]
+    #[test_case("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1" ; "en passant")]
+    fn test_push_null(input_fen: &str) {
+        let mut board = Board::parse_fen(input_fen).unwrap();
+        let original = board.clone();
+        board.push_null();
+        let mut fields: Vec<&str> = input_fen.split_whitespace().collect();
+        fields[1] = if fields[1] == "w" { "b" } else { "w" };
+        fields[3] = "-";
+        let expected = Board::parse_fen(&fields.join(" ")).unwrap();
+        assert_eq!(board.side_to_move(), expected.side_to_move());
+        assert_eq!(board.hash(), expected.hash());
+        assert_eq!(board.legal_moves(), expected.legal_moves());
+        board.pop_null();
+        assert_eq!(board, original);
+    }
+
+    #[test_case("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", Side::White, false ; "pawns only")]
+    #[test_case("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", Side::Black, false ; "bare king")]
+    #[test_case("4k3/8/8/8/8/8/4P3/3NK3 w - - 0 1", Side::White, true ; "knight")]
+    fn test_has_non_pawn_material(fen: &str, side: Side, expected: bool) {
+        let board = Board::parse_fen(fen).unwrap();
+        assert_eq!(board.has_non_pawn_material(side), expected);
+    }
+
+    #[test_case("", 0x463b96181691fc9c ; "starting position")]
+    #[test_case("e2e4", 0x823c9b50fd114196 ; "e4")]
+    #[test_case("e2e4 d7d5", 0x0756b94461c50fb0 ; "e4 d5")]
+    #[test_case("e2e4 d7d5 e4e5", 0x662fafb965db29d4 ; "e4 d5 e5")]
+    #[test_case("e2e4 d7d5 e4e5 f7f5", 0x22a48b5a8e47ff78 ; "e4 d5 e5 f5")]
+    #[test_case("e2e4 d7d5 e4e5 f7f5 e1e2", 0x652a607ca3f242c1 ; "e4 d5 e5 f5 Ke2")]
+    #[test_case("e2e4 d7d5 e4e5 f7f5 e1e2 e8f7", 0x00fdd303c946bdd9 ; "e4 d5 e5 f5 Ke2 Kf7")]
+    #[test_case("a2a4 b7b5 h2h4 b5b4 c2c4", 0x3c8123ea7b067637 ; "a4 b5 h4 b4 c4")]
+    #[test_case("a2a4 b7b5 h2h4 b5b4 c2c4 b4c3 a1a3", 0x5c3f9b829b279560 ; "a4 b5 h4 b4 c4 bxc3 Ra3")]
+    fn test_polyglot_hash(uci_moves: &str, expected_hash: u64) {
+        let mut board = Board::starting_position();
+        for uci_move in uci_moves.split_whitespace() {
+            board.push_uci(uci_move).unwrap();
+        }
+        assert_eq!(board.hash(), expected_hash);
+        assert_eq!(Board::parse_fen(&board.to_fen_string()).unwrap().hash(), expected_hash);
+        for _ in uci_moves.split_whitespace() {
+            board.pop();
+        }
+        assert_eq!(board.hash(), 0x463b96181691fc9c);
+    }
+
+    #[test_case("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1", true ; "no pawn next to the pushed pawn")]
+    #[test_case("4k3/8/8/8/3pP3/8/8/4K3 w - d3 0 1", true ; "pawn of the wrong side")]
+    #[test_case("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1", false ; "capturable")]
+    fn test_polyglot_ep_hash(fen: &str, same_as_without_ep: bool) {
+        let board = Board::parse_fen(fen).unwrap();
+        let mut fields: Vec<&str> = fen.split_whitespace().collect();
+        fields[3] = "-";
+        let without_ep = Board::parse_fen(&fields.join(" ")).unwrap();
+        assert_eq!(board.hash() == without_ep.hash(), same_as_without_ep);
+    }
+
+    #[test_case(POSITION_1 ; "position 1")]
+    #[test_case(POSITION_2 ; "position 2")]
+    #[test_case(POSITION_3 ; "position 3")]
+    #[test_case(POSITION_4 ; "position 4")]
+    #[test_case(POSITION_5 ; "position 5")]
+    #[test_case(POSITION_6 ; "position 6")]
+    #[test_case(POSITION_7 ; "position 7")]
+    #[test_case(POSITION_8 ; "position 8")]
+    fn test_san_round_trip(input_fen: &str) {
+        let board = Board::parse_fen(input_fen).unwrap();
+        for move_ in board.legal_moves().iter() {
+            let san = move_.pgn_spec(&board);
+            assert_eq!(board.parse_san(&san), Ok(*move_), "{}", san);
+        }
+    }
+
+    #[test_case(POSITION_1, "Nf3", "g1f3" ; "knight move")]
+    #[test_case(POSITION_1, "e4", "e2e4" ; "pawn double push")]
+    #[test_case(POSITION_2, "O-O", "e1g1" ; "short castling")]
+    #[test_case(POSITION_2, "0-0-0", "e1c1" ; "long castling with zeros")]
+    #[test_case(POSITION_2, "Bxa6", "e2a6" ; "bishop capture")]
+    #[test_case(POSITION_2, "Qxf6!?", "f3f6" ; "capture with annotation")]
+    #[test_case(POSITION_2, "Ng4", "e5g4" ; "knight unambiguous")]
+    #[test_case("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "bxa8=Q+", "b7a8q" ; "capture promotion with check")]
+    #[test_case("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "bxa8N", "b7a8n" ; "capture promotion without equals sign")]
+    #[test_case("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b8=B", "b7b8b" ; "push promotion")]
+    #[test_case(POSITION_5, "dxc8=R", "d7c8r" ; "capture promotion to rook")]
+    #[test_case(POSITION_6, "Nd5", "c3d5" ; "knight move in open position")]
+    #[test_case(POSITION_7, "exf6", "e5f6" ; "en passant capture")]
+    #[test_case("7k/8/8/8/8/8/8/RK5R w - - 0 1", "Rhd1", "h1d1" ; "file disambiguation")]
+    #[test_case("R6k/8/8/8/8/8/8/RK6 w - - 0 1", "R1a5", "a1a5" ; "rank disambiguation")]
+    #[test_case("7k/8/8/8/8/8/8/QK5Q w - - 0 1", "Qa1d4#", "a1d4" ; "square disambiguation")]
+    fn test_parse_san(input_fen: &str, san: &str, uci_move: &str) {
+        let mut board = Board::parse_fen(input_fen).unwrap();
+        let move_ = board.parse_san(san).unwrap();
+        assert_eq!(move_.to_uci_string(), uci_move);
+        assert_eq!(board.push_san(san), Ok(move_));
+    }
+
+    #[test_case(POSITION_1, "Nf4", SanParseError::IllegalMove("Nf4".to_string()) ; "unreachable square")]
+    #[test_case(POSITION_1, "O-O", SanParseError::IllegalMove("O-O".to_string()) ; "castling through pieces")]
+    #[test_case(POSITION_1, "exd3", SanParseError::IllegalMove("exd3".to_string()) ; "capture of empty square")]
+    #[test_case(POSITION_2, "Ne2", SanParseError::IllegalMove("Ne2".to_string()) ; "capture of own piece")]
+    #[test_case(POSITION_6, "Rd1", SanParseError::AmbiguousMove("Rd1".to_string()) ; "ambiguous rook move")]
+    #[test_case("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "bxa8", SanParseError::AmbiguousMove("bxa8".to_string()) ; "missing promotion piece")]
+    #[test_case("4k3/8/8/4p3/3P4/8/8/4K3 w - - 0 1", "e5", SanParseError::IllegalMove("e5".to_string()) ; "capture without x")]
+    #[test_case("4k3/8/8/8/8/8/8/4K3 w - - 0 1", "Kxd1", SanParseError::IllegalMove("Kxd1".to_string()) ; "x without capture")]
+    #[test_case(POSITION_1, "Nz3", SanParseError::InvalidSyntax("Nz3".to_string()) ; "bad square")]
+    #[test_case(POSITION_1, "Ng1=Q", SanParseError::InvalidSyntax("Ng1=Q".to_string()) ; "piece promotion")]
+    fn test_parse_san_errors(input_fen: &str, san: &str, expected_error: SanParseError) {
+        let board = Board::parse_fen(input_fen).unwrap();
+        assert_eq!(board.parse_san(san), Err(expected_error));
+    }
+
+    #[test_case(POSITION_1, None ; "ongoing")]
+    #[test_case("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", Some(Outcome::Stalemate) ; "stalemate")]
+    #[test_case("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1", Some(Outcome::Checkmate { winner: Side::White }) ; "checkmate")]
+    #[test_case("8/8/4k3/8/8/3K4/8/8 w - - 0 1", Some(Outcome::InsufficientMaterial) ; "KvK")]
+    #[test_case("8/8/4k3/8/8/3K4/3B4/8 w - - 0 1", Some(Outcome::InsufficientMaterial) ; "KBvK")]
+    #[test_case("8/8/4kn2/8/8/3K4/8/8 b - - 0 1", Some(Outcome::InsufficientMaterial) ; "KvKN")]
+    #[test_case("8/8/4kb2/8/8/3K4/3B4/8 w - - 0 1", Some(Outcome::InsufficientMaterial) ; "same colour bishops")]
+    #[test_case("8/8/4k1b1/8/8/3K4/3B4/8 w - - 0 1", None ; "opposite colour bishops")]
+    #[test_case("8/8/4k3/8/8/3K4/3BN3/8 w - - 0 1", None ; "KBNvK")]
+    #[test_case("8/8/4kn2/8/8/3K4/3N4/8 w - - 0 1", None ; "KNvKN")]
+    #[test_case("8/8/4k3/8/8/3K4/3P4/8 w - - 0 1", None ; "KPvK")]
+    #[test_case("8/8/4k3/8/8/3K4/3R4/8 w - - 99 80", None ; "ninety nine half moves")]
+    #[test_case("8/8/4k3/8/8/3K4/3R4/8 w - - 100 80", Some(Outcome::FiftyMoveRule) ; "fifty move rule")]
+    #[test_case("8/8/4k3/8/8/3K4/3R4/8 w - - 150 80", Some(Outcome::SeventyFiveMoveRule) ; "seventy five move rule")]
+    #[test_case("7k/6Q1/6K1/8/8/8/8/8 b - - 150 80", Some(Outcome::Checkmate { winner: Side::White }) ; "checkmate beats seventy five move rule")]
+    fn test_outcome(input_fen: &str, expected_outcome: Option<Outcome>) {
+        let board = Board::parse_fen(input_fen).unwrap();
+        assert_eq!(board.outcome(), expected_outcome);
+    }
+
+    // a1 is a dark square, h1 a light one
+    #[test_case("a1", false)]
+    #[test_case("h1", true)]
+    #[test_case("a8", true)]
+    #[test_case("h8", false)]
+    #[test_case("d4", false)]
+    #[test_case("e4", true)]
+    fn test_light_squares(square: &str, is_light: bool) {
+        assert_eq!(BitBoard::new(LIGHT_SQUARES).is_occupied(square.parse().unwrap()), is_light);
+    }
+
+    #[test]
+    fn test_outcome_repetition() {
+        let mut board = Board::starting_position();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        for _ in 0..2 {
+            for &uci_move in shuffle.iter() {
+                assert_eq!(board.outcome(), None);
+                board.push_uci(uci_move).unwrap();
+            }
+        }
+        assert_eq!(board.outcome(), Some(Outcome::ThreefoldRepetition));
+        assert!(board.outcome().unwrap().is_claimable());
+        for _ in 0..2 {
+            for &uci_move in shuffle.iter() {
+                board.push_uci(uci_move).unwrap();
+            }
+        }
+        assert_eq!(board.outcome(), Some(Outcome::FivefoldRepetition));
+        assert!(!board.outcome().unwrap().is_claimable());
+        board.pop();
+        assert_eq!(board.outcome(), Some(Outcome::ThreefoldRepetition));
+    }
+
     #[test_case("2bq1rk1/1pppbp2/r1n2np1/pB1Pp2p/Q2NP3/2P2N2/PP3PPP/R1B1K2R w KQ - 0 1", "d5c6", 300 ; "SEE 1")]
     #[test_case("2bq1rk1/1pppbp2/r1n2np1/pB1Pp2p/Q3P3/2P2N2/PP2NPPP/R1B1K2R w KQ - 0 1", "d5c6", -800 ; "SEE 2")]
     #[test_case("r1bq1rk1/ppp1ppbp/n2p1np1/4P3/2PP4/2N2N2/PP2BPPP/R1BQ1RK1 b - - 0 1", "d6e5", 0 ; "SEE 3")]
@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+use rand::Rng;
+
+use crate::board::Board;
+use crate::{Move, Piece, Side};
+
+const ENTRY_SIZE: usize = 16;
+const PROMOTION_PIECES: [&str; 5] = ["", "n", "b", "r", "q"];
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    pub fn from_bytes(bytes: &[u8; ENTRY_SIZE]) -> Self {
+        BookEntry {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0u8; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+
+    // Polyglot packs to file, to rank, from file, from rank and promotion piece into 3 bits each,
+    // castling is encoded as the king capturing its own rook
+    fn uci_move(&self) -> String {
+        let square = |bits: u16| {
+            let file = (b'a' + (bits & 7) as u8) as char;
+            let rank = (b'1' + ((bits >> 3) & 7) as u8) as char;
+            format!("{}{}", file, rank)
+        };
+        let promotion = PROMOTION_PIECES.get((self.raw_move >> 12 & 7) as usize).unwrap_or(&"");
+        format!("{}{}{}", square(self.raw_move >> 6), square(self.raw_move), promotion)
+    }
+
+    pub fn encode_move(move_: &Move) -> u16 {
+        let to = match move_ {
+            Move::ShortCastling(m) | Move::LongCastling(m) => m.rook_from(),
+            _ => move_.to(),
+        };
+        let promotion = match move_.promotion_piece() {
+            Some(Piece::Knight) => 1,
+            Some(Piece::Bishop) => 2,
+            Some(Piece::Rook) => 3,
+            Some(Piece::Queen) => 4,
+            _ => 0,
+        };
+        promotion << 12 | (move_.from().ordinal as u16) << 6 | to.ordinal as u16
+    }
+
+    pub fn decode_move(&self, board: &Board) -> Option<Move> {
+        let uci_move = self.uci_move();
+        board.legal_moves().into_iter().find(|m| match m {
+            Move::ShortCastling(_) | Move::LongCastling(_) => m.to_uci_string_chess960() == uci_move,
+            _ => m.to_uci_string() == uci_move,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PolyglotBook {
+    entries: Vec<BookEntry>,
+}
+
+impl PolyglotBook {
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        PolyglotBook::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let mut entries = vec![];
+        let mut bytes = [0u8; ENTRY_SIZE];
+        loop {
+            match reader.read_exact(&mut bytes) {
+                Ok(()) => entries.push(BookEntry::from_bytes(&bytes)),
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+        }
+        // books are sorted by key, but don't rely on it
+        Ok(PolyglotBook::from_entries(entries))
+    }
+
+    pub fn from_entries(mut entries: Vec<BookEntry>) -> Self {
+        entries.sort_by_key(|e| e.key);
+        PolyglotBook { entries }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        for entry in self.entries.iter() {
+            writer.write_all(&entry.to_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self, board: &Board) -> &[BookEntry] {
+        let key = board.hash();
+        let start = self.entries.partition_point(|e| e.key < key);
+        let end = self.entries.partition_point(|e| e.key <= key);
+        &self.entries[start..end]
+    }
+
+    // (move, weight) for every legal move the book has for the position
+    pub fn moves(&self, board: &Board) -> Vec<(Move, u16)> {
+        self.entries(board)
+            .iter()
+            .filter_map(|e| e.decode_move(board).map(|m| (m, e.weight)))
+            .collect()
+    }
+
+    pub fn weighted_move<R: Rng>(&self, board: &Board, rng: &mut R) -> Option<Move> {
+        let moves = self.moves(board);
+        let total_weight: u32 = moves.iter().map(|&(_, w)| w as u32).sum();
+        if total_weight == 0 {
+            return None;
+        }
+        let mut pick = rng.gen_range(0..total_weight);
+        for (m, weight) in moves {
+            if pick < weight as u32 {
+                return Some(m);
+            }
+            pick -= weight as u32;
+        }
+        None
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+struct MoveStats {
+    wins: u32,
+    draws: u32,
+    losses: u32,
+}
+
+impl MoveStats {
+    fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    // Polyglot's scoring: 2 points for a win and 1 for a draw
+    fn score(&self) -> u32 {
+        2 * self.wins + self.draws
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PolyglotBookBuilder {
+    max_ply: usize,
+    min_games: u32,
+    // minimum share of the points (0.0 - 1.0) scored by the side playing the move
+    min_score: f64,
+    stats: HashMap<(u64, u16), MoveStats>,
+    games: usize,
+}
+
+impl PolyglotBookBuilder {
+    pub fn new(max_ply: usize, min_games: u32, min_score: f64) -> Self {
+        PolyglotBookBuilder {
+            max_ply,
+            min_games,
+            min_score,
+            stats: HashMap::new(),
+            games: 0,
+        }
+    }
+
+    pub fn games(&self) -> usize {
+        self.games
+    }
+
+    // games without a decisive or drawn result are ignored
+    pub fn add_game(&mut self, starting_position: &Board, moves: &[Move], result: &str) -> bool {
+        let winner = match result {
+            "1-0" => Some(Side::White),
+            "0-1" => Some(Side::Black),
+            "1/2-1/2" => None,
+            _ => return false,
+        };
+        let mut board = starting_position.clone();
+        for move_ in moves.iter().take(self.max_ply) {
+            let stats = self.stats.entry((board.hash(), BookEntry::encode_move(move_))).or_default();
+            match winner {
+                Some(side) if side == board.side_to_move() => stats.wins += 1,
+                Some(_) => stats.losses += 1,
+                None => stats.draws += 1,
+            }
+            board.push(move_);
+        }
+        self.games += 1;
+        true
+    }
+
+    pub fn build(&self) -> PolyglotBook {
+        let mut by_position: HashMap<u64, Vec<(u16, MoveStats)>> = HashMap::new();
+        for (&(key, raw_move), &stats) in self.stats.iter() {
+            let games = stats.games();
+            if games < self.min_games || (stats.score() as f64) < self.min_score * 2.0 * games as f64 {
+                continue;
+            }
+            by_position.entry(key).or_default().push((raw_move, stats));
+        }
+        let mut entries = vec![];
+        for (key, moves) in by_position {
+            // scale down so the best move's score fits into the 16 bit weight
+            let max_score = moves.iter().map(|(_, stats)| stats.score()).max().unwrap_or(0);
+            let divisor = max_score.div_ceil(u16::MAX as u32).max(1);
+            for (raw_move, stats) in moves {
+                let weight = (stats.score() / divisor) as u16;
+                if weight > 0 {
+                    entries.push(BookEntry { key, raw_move, weight, learn: 0 });
+                }
+            }
+        }
+        // deterministic order of moves within a position
+        entries.sort_by_key(|e| (e.key, std::cmp::Reverse(e.weight), e.raw_move));
+        PolyglotBook { entries }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::io::Cursor;
+    use test_case::test_case;
+
+    fn raw_move(from: &str, to: &str, promotion: u16) -> u16 {
+        let square = |s: &str| {
+            let bytes = s.as_bytes();
+            ((bytes[1] - b'1') as u16) << 3 | (bytes[0] - b'a') as u16
+        };
+        promotion << 12 | square(from) << 6 | square(to)
+    }
+
+    fn book(entries: &[(&Board, u16, u16)]) -> PolyglotBook {
+        let mut bytes = vec![];
+        for &(board, raw_move, weight) in entries.iter().rev() {
+            let entry = BookEntry { key: board.hash(), raw_move, weight, learn: 0 };
+            bytes.extend_from_slice(&entry.to_bytes());
+        }
+        PolyglotBook::read(Cursor::new(bytes)).unwrap()
+    }
+
+    #[test]
+    fn test_entry_round_trip() {
+        let entry = BookEntry { key: 0x463b96181691fc9c, raw_move: 0x031c, weight: 7, learn: 42 };
+        assert_eq!(BookEntry::from_bytes(&entry.to_bytes()), entry);
+        assert_eq!(entry.to_bytes()[..8], [0x46, 0x3b, 0x96, 0x18, 0x16, 0x91, 0xfc, 0x9c]);
+    }
+
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "e2", "e4", 0, "e2e4" ; "pawn push")]
+    #[test_case("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1", "h1", 0, "e1g1" ; "short castling")]
+    #[test_case("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8", "a8", 0, "e8c8" ; "long castling")]
+    #[test_case("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7", "b8", 1, "b7b8n" ; "knight promotion")]
+    #[test_case("2k5/8/8/8/8/8/8/1RK2R2 w FB - 0 1", "c1", "f1", 0, "c1g1" ; "chess960 castling")]
+    fn test_decode_move(fen: &str, from: &str, to: &str, promotion: u16, expected_uci: &str) {
+        let board = Board::parse_fen(fen).unwrap();
+        let entry = BookEntry { key: board.hash(), raw_move: raw_move(from, to, promotion), weight: 1, learn: 0 };
+        assert_eq!(entry.decode_move(&board).unwrap().to_uci_string(), expected_uci);
+    }
+
+    #[test]
+    fn test_lookup() {
+        let start = Board::starting_position();
+        let mut after_e4 = start.clone();
+        after_e4.push_uci("e2e4").unwrap();
+        let book = book(&[
+            (&start, raw_move("e2", "e4", 0), 3),
+            (&after_e4, raw_move("c7", "c5", 0), 1),
+            (&start, raw_move("d2", "d4", 0), 1),
+            // not legal in the position, so it is skipped
+            (&start, raw_move("e2", "e5", 0), 1),
+        ]);
+        assert_eq!(book.len(), 4);
+        assert_eq!(book.entries(&start).len(), 3);
+        let moves: Vec<(String, u16)> = book.moves(&start).iter().map(|(m, w)| (m.to_uci_string(), *w)).collect();
+        assert_eq!(moves, vec![("d2d4".to_string(), 1), ("e2e4".to_string(), 3)]);
+        assert_eq!(book.moves(&after_e4).len(), 1);
+        assert!(book.moves(&Board::parse_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_weighted_move() {
+        let start = Board::starting_position();
+        let book = book(&[(&start, raw_move("e2", "e4", 0), 3), (&start, raw_move("d2", "d4", 0), 1), (&start, raw_move("c2", "c4", 0), 0)]);
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut e4_count = 0;
+        for _ in 0..1000 {
+            match book.weighted_move(&start, &mut rng).unwrap().to_uci_string().as_str() {
+                "e2e4" => e4_count += 1,
+                "d2d4" => {}
+                other => panic!("unexpected book move {}", other),
+            }
+        }
+        assert!((650..850).contains(&e4_count));
+        assert_eq!(PolyglotBook::default().weighted_move(&start, &mut rng), None);
+    }
+
+    #[test_case("e2e4", "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1" ; "pawn push")]
+    #[test_case("e1h1", "4k3/8/8/8/8/8/8/4K2R w K - 0 1" ; "short castling")]
+    #[test_case("e8a8", "r3k3/8/8/8/8/8/8/4K3 b q - 0 1" ; "long castling")]
+    #[test_case("a2a1q", "4k3/8/8/8/8/8/p7/4K3 b - - 0 1" ; "queen promotion")]
+    fn test_encode_move(uci_move: &str, fen: &str) {
+        let board = Board::parse_fen(fen).unwrap();
+        let move_ = board.get_uci_move(uci_move).unwrap();
+        let entry = BookEntry { key: board.hash(), raw_move: BookEntry::encode_move(&move_), weight: 1, learn: 0 };
+        assert_eq!(entry.uci_move(), uci_move);
+        assert_eq!(entry.decode_move(&board), Some(move_));
+    }
+
+    fn build(pgn: &str, max_ply: usize, min_games: u32, min_score: f64) -> PolyglotBook {
+        let mut builder = PolyglotBookBuilder::new(max_ply, min_games, min_score);
+        for game in crate::pgn::PgnReader::new(Cursor::new(pgn)) {
+            let game = game.unwrap();
+            builder.add_game(&game.starting_position, &game.moves, &game.result);
+        }
+        builder.build()
+    }
+
+    const GAMES: &str = "1. e4 e5 2. Nf3 1-0\n\n1. e4 e5 2. Bc4 1/2-1/2\n\n1. e4 c5 0-1\n\n1. d4 d5 1-0\n\n1. c4 *\n\n";
+
+    fn book_moves(book: &PolyglotBook, uci_moves: &str) -> Vec<(String, u16)> {
+        let mut board = Board::starting_position();
+        for uci_move in uci_moves.split_whitespace() {
+            board.push_uci(uci_move).unwrap();
+        }
+        book.moves(&board).iter().map(|(m, w)| (m.to_uci_string(), *w)).collect()
+    }
+
+    #[test]
+    fn test_build_weights() {
+        let book = build(GAMES, 100, 1, 0.0);
+        // e4: 1 win, 1 draw, 1 loss; d4: 1 win; the unfinished c4 game is ignored
+        assert_eq!(book_moves(&book, ""), vec![("e2e4".to_string(), 3), ("d2d4".to_string(), 2)]);
+        // scores are from the point of view of the side to move, c5 won once, e5 lost once and drew once
+        assert_eq!(book_moves(&book, "e2e4"), vec![("c7c5".to_string(), 2), ("e7e5".to_string(), 1)]);
+        assert_eq!(book_moves(&book, "e2e4 e7e5"), vec![("g1f3".to_string(), 2), ("f1c4".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_build_filters() {
+        assert!(book_moves(&build(GAMES, 1, 1, 0.0), "e2e4").is_empty());
+        assert_eq!(book_moves(&build(GAMES, 100, 2, 0.0), ""), vec![("e2e4".to_string(), 3)]);
+        assert_eq!(book_moves(&build(GAMES, 100, 1, 0.6), ""), vec![("d2d4".to_string(), 2)]);
+        // losing moves never get any weight
+        assert_eq!(book_moves(&build(GAMES, 100, 1, 0.0), "d2d4"), vec![]);
+    }
+
+    #[test]
+    fn test_write_and_read() {
+        let book = build(GAMES, 100, 1, 0.0);
+        let mut bytes = vec![];
+        book.write(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), book.len() * ENTRY_SIZE);
+        let keys: Vec<u64> = bytes.chunks(ENTRY_SIZE).map(|c| u64::from_be_bytes(c[..8].try_into().unwrap())).collect();
+        assert!(keys.windows(2).all(|w| w[0] <= w[1]));
+        let read = PolyglotBook::read(Cursor::new(bytes)).unwrap();
+        assert_eq!(read.entries, book.entries);
+    }
+}
@@ -0,0 +1,728 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::board::Board;
+use crate::errors::EndgameTableError;
+use crate::square::Square;
+use crate::{Move, Piece, Side};
+
+const MAGIC: &[u8; 4] = b"CHEG";
+const VERSION: u8 = 1;
+const EXTENSION: &str = "dtm";
+pub const MAX_PIECES: usize = 4;
+
+const PIECE_ORDER: [Piece; 6] = [Piece::King, Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight, Piece::Pawn];
+
+// markers for the moves left to refute while generating a table
+const ILLEGAL: u8 = 255;
+const NEVER_LOST: u8 = 254;
+
+// Distance to mate in plies from the point of view of the side to move
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Dtm {
+    Draw,
+    Win(u8),
+    Loss(u8),
+}
+
+impl Dtm {
+    // stored as a signed byte: 0 for draws, n for mate in n plies, -n - 1 for mated in n plies
+    fn from_stored(value: i8) -> Dtm {
+        match value {
+            0 => Dtm::Draw,
+            v if v > 0 => Dtm::Win(v as u8),
+            v => Dtm::Loss((-(v as i16) - 1) as u8),
+        }
+    }
+
+    // None if the distance does not fit
+    fn to_stored(self) -> Option<i8> {
+        let value = match self {
+            Dtm::Draw => 0,
+            Dtm::Win(plies) => plies as i16,
+            Dtm::Loss(plies) => -(plies as i16) - 1,
+        };
+        i8::try_from(value).ok()
+    }
+
+    // the value of the position before the move that led to a position with this value
+    pub fn parent(self) -> Dtm {
+        match self {
+            Dtm::Draw => Dtm::Draw,
+            Dtm::Win(plies) => Dtm::Loss(plies + 1),
+            Dtm::Loss(plies) => Dtm::Win(plies + 1),
+        }
+    }
+
+    // higher is better for the side to move
+    fn rank(self) -> i32 {
+        match self {
+            Dtm::Draw => 0,
+            Dtm::Win(plies) => 1000 - plies as i32,
+            Dtm::Loss(plies) => -1000 + plies as i32,
+        }
+    }
+}
+
+fn piece_order(piece: Piece) -> usize {
+    PIECE_ORDER.iter().position(|&p| p == piece).unwrap()
+}
+
+fn side_name(pieces: &[Piece]) -> String {
+    pieces.iter().map(|p| p.to_fen_char(Side::White)).collect()
+}
+
+fn parse_side(name: &str) -> Option<Vec<Piece>> {
+    let mut pieces = name
+        .chars()
+        .map(|c| PIECE_ORDER.iter().copied().find(|p| p.to_fen_char(Side::White) == c))
+        .collect::<Option<Vec<Piece>>>()?;
+    pieces.sort_by_key(|&p| piece_order(p));
+    if pieces.iter().filter(|&&p| p == Piece::King).count() != 1 {
+        return None;
+    }
+    Some(pieces)
+}
+
+fn board_side(board: &Board, side: Side) -> Vec<Piece> {
+    let mut pieces: Vec<Piece> = board.get_pieces(side).filter_map(|square| board.get_piece(side, square)).collect();
+    pieces.sort_by_key(|&p| piece_order(p));
+    pieces
+}
+
+// The table name for some material puts the stronger side first, as white. Also returns
+// whether the colours had to be swapped.
+fn canonical_name(white: &[Piece], black: &[Piece]) -> (String, bool) {
+    let strength = |pieces: &[Piece]| (Reverse(pieces.len()), pieces.iter().map(|&p| piece_order(p)).collect::<Vec<_>>());
+    let flipped = strength(black) < strength(white);
+    let (first, second) = if flipped { (black, white) } else { (white, black) };
+    (format!("{}v{}", side_name(first), side_name(second)), flipped)
+}
+
+fn is_insufficient_material(white: &[Piece], black: &[Piece]) -> bool {
+    let (weaker, stronger) = if white.len() < black.len() { (white, black) } else { (black, white) };
+    weaker.len() == 1 && (stronger.len() == 1 || (stronger.len() == 2 && matches!(stronger[1], Piece::Bishop | Piece::Knight)))
+}
+
+// Where a piece standing on `square` can have come from with a non-capturing move
+fn unmove_origins(side: Side, piece: Piece, square: Square, occupied: u64) -> Vec<u8> {
+    let is_empty = |s: &Square| occupied & s.bit() == 0;
+    let step = |deltas: &[(i8, i8)]| {
+        deltas.iter().filter_map(|&(dr, df)| square.delta(dr, df)).filter(is_empty).map(|s| s.ordinal).collect()
+    };
+    let slide = |deltas: &[(i8, i8)]| {
+        let mut origins = vec![];
+        for &(dr, df) in deltas {
+            let mut current = square;
+            while let Some(s) = current.delta(dr, df).filter(is_empty) {
+                origins.push(s.ordinal);
+                current = s;
+            }
+        }
+        origins
+    };
+    const KNIGHT: [(i8, i8); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+    const ALL_DIRECTIONS: [(i8, i8); 8] = [(1, 1), (1, -1), (-1, 1), (-1, -1), (1, 0), (-1, 0), (0, 1), (0, -1)];
+    const DIAGONALS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+    const LINES: [(i8, i8); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+    match piece {
+        Piece::Pawn => {
+            let (backward, double_push_rank) = match side {
+                Side::White => (-1, 3),
+                Side::Black => (1, 4),
+            };
+            let mut origins = vec![];
+            if let Some(one) = square.delta(backward, 0).filter(|s| is_empty(s) && s.rank() != 0 && s.rank() != 7) {
+                origins.push(one.ordinal);
+                if square.rank() == double_push_rank {
+                    if let Some(two) = one.delta(backward, 0).filter(is_empty) {
+                        origins.push(two.ordinal);
+                    }
+                }
+            }
+            origins
+        }
+        Piece::Knight => step(&KNIGHT),
+        Piece::King => step(&ALL_DIRECTIONS),
+        Piece::Bishop => slide(&DIAGONALS),
+        Piece::Rook => slide(&LINES),
+        Piece::Queen => slide(&ALL_DIRECTIONS),
+    }
+}
+
+// bit 1 of the symmetry flips the ranks and bit 0 the files
+fn transform(square: u8, symmetry: usize) -> u8 {
+    let mut square = square;
+    if symmetry & 2 != 0 {
+        square ^= 56;
+    }
+    if symmetry & 1 != 0 {
+        square ^= 7;
+    }
+    square
+}
+
+// Distance to mate for every placement of the pieces of one material signature, indexed by
+// the side to move and the square of each piece. Positions are mirrored so that the white
+// king is in the a1-d4 quarter of the board, or on files a-d if there are pawns; the mirroring
+// is then unique, so each position has exactly one index. En passant captures are ignored.
+pub struct EndgameTable {
+    name: String,
+    pieces: Vec<(Side, Piece)>,
+    symmetries: usize,
+    king_squares: Vec<u8>,
+    king_codes: [Option<usize>; 64],
+    values: Vec<i8>,
+}
+
+impl EndgameTable {
+    fn empty(name: &str) -> Result<Self, EndgameTableError> {
+        let invalid = || EndgameTableError::InvalidMaterial(name.to_string());
+        let (white, black) = name.split_once('v').ok_or_else(invalid)?;
+        let white = parse_side(white).ok_or_else(invalid)?;
+        let black = parse_side(black).ok_or_else(invalid)?;
+        if white.len() + black.len() > MAX_PIECES || canonical_name(&white, &black).0 != name {
+            return Err(invalid());
+        }
+        let pieces: Vec<(Side, Piece)> = white
+            .iter()
+            .map(|&p| (Side::White, p))
+            .chain(black.iter().map(|&p| (Side::Black, p)))
+            .collect();
+        let has_pawns = pieces.iter().any(|&(_, piece)| piece == Piece::Pawn);
+        let king_squares: Vec<u8> = (0..64)
+            .filter(|&square| square % 8 < 4 && (has_pawns || square / 8 < 4))
+            .collect();
+        let mut king_codes = [None; 64];
+        for (code, &square) in king_squares.iter().enumerate() {
+            king_codes[square as usize] = Some(code);
+        }
+        let size = (2 * king_squares.len()) << (6 * (pieces.len() - 1));
+        Ok(EndgameTable {
+            name: name.to_string(),
+            pieces,
+            symmetries: if has_pawns { 2 } else { 4 },
+            king_squares,
+            king_codes,
+            values: vec![0; size],
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, EndgameTableError> {
+        let file = File::open(path).map_err(EndgameTableError::Io)?;
+        EndgameTable::read(BufReader::new(file))
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, EndgameTableError> {
+        let mut header = [0u8; 6];
+        reader.read_exact(&mut header).map_err(EndgameTableError::Io)?;
+        if &header[..4] != MAGIC || header[4] != VERSION {
+            return Err(EndgameTableError::InvalidFile("unknown format or version".to_string()));
+        }
+        let mut name = vec![0u8; header[5] as usize];
+        reader.read_exact(&mut name).map_err(EndgameTableError::Io)?;
+        let name = String::from_utf8(name).map_err(|_| EndgameTableError::InvalidFile("invalid name".to_string()))?;
+        let mut table = EndgameTable::empty(&name)?;
+        let mut values = vec![0u8; table.values.len()];
+        reader.read_exact(&mut values).map_err(EndgameTableError::Io)?;
+        table.values = values.into_iter().map(|v| v as i8).collect();
+        Ok(table)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), EndgameTableError> {
+        let mut writer = BufWriter::new(File::create(path).map_err(EndgameTableError::Io)?);
+        self.write(&mut writer).and_then(|_| writer.flush()).map_err(EndgameTableError::Io)
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION, self.name.len() as u8])?;
+        writer.write_all(self.name.as_bytes())?;
+        let values: Vec<u8> = self.values.iter().map(|&v| v as u8).collect();
+        writer.write_all(&values)
+    }
+
+    // identical pieces are interchangeable, so their squares are kept in ascending order
+    fn index(&self, side_to_move: Side, squares: &mut [u8]) -> usize {
+        let symmetry = (0..self.symmetries)
+            .find(|&symmetry| self.king_codes[transform(squares[0], symmetry) as usize].is_some())
+            .unwrap();
+        for square in squares.iter_mut() {
+            *square = transform(*square, symmetry);
+        }
+        let mut start = 0;
+        while start < squares.len() {
+            let end = start + self.pieces[start..].iter().take_while(|&&p| p == self.pieces[start]).count();
+            squares[start..end].sort();
+            start = end;
+        }
+        let stm = match side_to_move {
+            Side::White => 0,
+            Side::Black => 1,
+        };
+        let king = self.king_codes[squares[0] as usize].unwrap();
+        squares[1..].iter().fold(stm * self.king_squares.len() + king, |idx, &s| idx << 6 | s as usize)
+    }
+
+    fn decode(&self, idx: usize) -> (Side, Vec<u8>) {
+        let n = self.pieces.len();
+        let mut squares = vec![0; n];
+        let mut rest = idx;
+        for square in squares[1..].iter_mut().rev() {
+            *square = (rest & 63) as u8;
+            rest >>= 6;
+        }
+        squares[0] = self.king_squares[rest % self.king_squares.len()];
+        let side_to_move = if rest < self.king_squares.len() { Side::White } else { Side::Black };
+        (side_to_move, squares)
+    }
+
+    // the position stored at an index, unless it is illegal or stored at another index
+    fn position(&self, idx: usize) -> Option<Board> {
+        let (side_to_move, squares) = self.decode(idx);
+        if (1..squares.len()).any(|i| squares[..i].contains(&squares[i])) {
+            return None;
+        }
+        if self.index(side_to_move, &mut squares.clone()) != idx {
+            return None;
+        }
+        let pieces: Vec<(Side, Piece, Square)> = self
+            .pieces
+            .iter()
+            .zip(squares.iter())
+            .map(|(&(side, piece), &square)| (side, piece, Square::from_ordinal(square)))
+            .collect();
+        let board = Board::from_pieces(side_to_move, &pieces);
+        board.validate().ok()?;
+        Some(board)
+    }
+
+    fn board_index(&self, board: &Board, flipped: bool) -> usize {
+        let mut squares = Vec::with_capacity(self.pieces.len());
+        for (i, &(side, piece)) in self.pieces.iter().enumerate() {
+            if i > 0 && self.pieces[i - 1] == (side, piece) {
+                continue;
+            }
+            let side = if flipped { side.opposite() } else { side };
+            for square in board.get_pieces(side) {
+                if board.get_piece(side, square) == Some(piece) {
+                    squares.push(square.ordinal ^ if flipped { 56 } else { 0 });
+                }
+            }
+        }
+        let side_to_move = if flipped { board.side_to_move().opposite() } else { board.side_to_move() };
+        self.index(side_to_move, &mut squares)
+    }
+
+    fn probe(&self, board: &Board, flipped: bool) -> Dtm {
+        Dtm::from_stored(self.values[self.board_index(board, flipped)])
+    }
+
+    // the positions from which a non-capturing, non-promoting move leads to this one
+    fn predecessors(&self, idx: usize) -> Vec<usize> {
+        let (side_to_move, squares) = self.decode(idx);
+        let mover = side_to_move.opposite();
+        let occupied = squares.iter().fold(0u64, |acc, &s| acc | 1 << s);
+        let mut result = vec![];
+        for (i, &(side, piece)) in self.pieces.iter().enumerate() {
+            if side != mover {
+                continue;
+            }
+            for origin in unmove_origins(side, piece, Square::from_ordinal(squares[i]), occupied) {
+                let mut origin_squares = squares.clone();
+                origin_squares[i] = origin;
+                result.push(self.index(mover, &mut origin_squares));
+            }
+        }
+        result
+    }
+}
+
+// A set of endgame tables which can generate the tables it is missing
+#[derive(Default)]
+pub struct EndgameTables {
+    tables: HashMap<String, EndgameTable>,
+}
+
+impl EndgameTables {
+    pub fn new() -> Self {
+        EndgameTables::default()
+    }
+
+    // loads all the tables in a directory
+    pub fn open_dir<P: AsRef<Path>>(path: P) -> Result<Self, EndgameTableError> {
+        let mut tables = EndgameTables::new();
+        for entry in std::fs::read_dir(path).map_err(EndgameTableError::Io)? {
+            let path = entry.map_err(EndgameTableError::Io)?.path();
+            if path.extension().is_some_and(|extension| extension == EXTENSION) {
+                tables.insert(EndgameTable::open(path)?);
+            }
+        }
+        Ok(tables)
+    }
+
+    pub fn save_dir<P: AsRef<Path>>(&self, path: P) -> Result<(), EndgameTableError> {
+        for table in self.tables.values() {
+            table.save(path.as_ref().join(format!("{}.{}", table.name, EXTENSION)))?;
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, table: EndgameTable) {
+        self.tables.insert(table.name.clone(), table);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&EndgameTable> {
+        self.tables.get(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.tables.keys().map(|name| name.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    pub fn max_pieces(&self) -> usize {
+        self.tables.values().map(|table| table.pieces.len()).max().unwrap_or(0)
+    }
+
+    // the value of a position, ignoring en passant
+    fn lookup(&self, board: &Board) -> Option<Dtm> {
+        if board.has_castling_rights() {
+            return None;
+        }
+        let white = board_side(board, Side::White);
+        let black = board_side(board, Side::Black);
+        if is_insufficient_material(&white, &black) {
+            return Some(Dtm::Draw);
+        }
+        if white.len() + black.len() > self.max_pieces() {
+            return None;
+        }
+        let (name, flipped) = canonical_name(&white, &black);
+        Some(self.tables.get(&name)?.probe(board, flipped))
+    }
+
+    pub fn probe(&self, board: &Board) -> Option<Dtm> {
+        let dtm = self.lookup(board)?;
+        // the tables do not know about en passant rights
+        let is_en_passant = |m: &Move| matches!(m, Move::EnPassantCapture(_));
+        if board.get_piece_count(Piece::Pawn) > 1 && board.legal_moves().iter().any(is_en_passant) {
+            return None;
+        }
+        Some(dtm)
+    }
+
+    // the moves that keep the best distance to mate
+    pub fn root_moves(&self, board: &mut Board) -> Option<Vec<Move>> {
+        self.probe(board)?;
+        let mut ranked = vec![];
+        for move_ in board.legal_moves() {
+            board.push(&move_);
+            let dtm = self.probe(board);
+            board.pop();
+            ranked.push((move_, dtm?.parent().rank()));
+        }
+        let best_rank = ranked.iter().map(|&(_, rank)| rank).max()?;
+        Some(ranked.into_iter().filter(|&(_, rank)| rank == best_rank).map(|(move_, _)| move_).collect())
+    }
+
+    // the tables a capture or promotion can lead to
+    fn dependencies(name: &str) -> Vec<String> {
+        let (white, black) = name.split_once('v').unwrap();
+        let sides = [parse_side(white).unwrap(), parse_side(black).unwrap()];
+        let mut results = vec![];
+        for (side, other) in [(0, 1), (1, 0)] {
+            // captures
+            for i in 1..sides[other].len() {
+                let mut captured = sides[other].clone();
+                captured.remove(i);
+                results.push((sides[side].clone(), captured));
+            }
+            // promotions, with or without capture
+            if let Some(pawn) = sides[side].iter().position(|&p| p == Piece::Pawn) {
+                for promotion in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
+                    let mut promoted = sides[side].clone();
+                    promoted[pawn] = promotion;
+                    promoted.sort_by_key(|&p| piece_order(p));
+                    results.push((promoted.clone(), sides[other].clone()));
+                    for i in 1..sides[other].len() {
+                        let mut captured = sides[other].clone();
+                        captured.remove(i);
+                        results.push((promoted.clone(), captured));
+                    }
+                }
+            }
+        }
+        let mut names: Vec<String> = results
+            .into_iter()
+            .filter(|(first, second)| !is_insufficient_material(first, second))
+            .map(|(first, second)| canonical_name(&first, &second).0)
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    // Generates the table for a material signature such as "KRvK" and any table it depends
+    // on, by retrograde analysis. Returns the names of the generated tables.
+    pub fn generate(&mut self, name: &str) -> Result<Vec<String>, EndgameTableError> {
+        if self.tables.contains_key(name) {
+            return Ok(vec![]);
+        }
+        let mut table = EndgameTable::empty(name)?;
+        let mut generated = vec![];
+        for dependency in EndgameTables::dependencies(name) {
+            generated.extend(self.generate(&dependency)?);
+        }
+        self.solve(&mut table)?;
+        self.insert(table);
+        generated.push(name.to_string());
+        Ok(generated)
+    }
+
+    fn solve(&self, table: &mut EndgameTable) -> Result<(), EndgameTableError> {
+        let size = table.values.len();
+        // in-table moves of each position not yet known to lose
+        let mut remaining = vec![ILLEGAL; size];
+        // the longest loss through captures and promotions, if they all lose
+        let mut conversion_loss = vec![0u8; size];
+        // positions to resolve, by distance to mate
+        let mut queue: Vec<Vec<u32>> = vec![];
+        let schedule = |queue: &mut Vec<Vec<u32>>, plies: u8, idx: usize| {
+            if queue.len() <= plies as usize {
+                queue.resize(plies as usize + 1, vec![]);
+            }
+            queue[plies as usize].push(idx as u32);
+        };
+
+        for idx in 0..size {
+            let mut board = match table.position(idx) {
+                Some(board) => board,
+                None => continue,
+            };
+            let moves = board.legal_moves();
+            if moves.is_empty() {
+                if board.is_in_check() {
+                    schedule(&mut queue, 0, idx);
+                }
+                remaining[idx] = NEVER_LOST;
+                continue;
+            }
+            let mut count = 0;
+            let mut never_lost = false;
+            let mut fastest_win = None;
+            let mut longest_loss = 0;
+            for move_ in moves.iter() {
+                if !move_.is_capture() && move_.promotion_piece().is_none() {
+                    count += 1;
+                    continue;
+                }
+                board.push(move_);
+                let dtm = self.lookup(&board).ok_or_else(|| EndgameTableError::InvalidMaterial(board.to_fen_string()));
+                board.pop();
+                let dtm = dtm?;
+                match dtm.parent() {
+                    Dtm::Win(plies) => {
+                        never_lost = true;
+                        fastest_win = Some(fastest_win.map_or(plies, |fastest: u8| fastest.min(plies)));
+                    }
+                    Dtm::Draw => never_lost = true,
+                    Dtm::Loss(plies) => longest_loss = longest_loss.max(plies),
+                }
+            }
+            if let Some(plies) = fastest_win {
+                schedule(&mut queue, plies, idx);
+            }
+            if never_lost {
+                remaining[idx] = NEVER_LOST;
+            } else {
+                remaining[idx] = count;
+                conversion_loss[idx] = longest_loss;
+                if count == 0 {
+                    schedule(&mut queue, longest_loss, idx);
+                }
+            }
+        }
+
+        // a position is won in n + 1 plies if it has a move to a position lost in n, and lost
+        // in n + 1 once its last move turned out to lead to a position won in n
+        let mut plies = 0;
+        while plies < queue.len() {
+            for idx in std::mem::take(&mut queue[plies]) {
+                let idx = idx as usize;
+                if table.values[idx] != 0 {
+                    continue;
+                }
+                let is_win = plies % 2 == 1;
+                let dtm = u8::try_from(plies).ok().map(|plies| if is_win { Dtm::Win(plies) } else { Dtm::Loss(plies) });
+                table.values[idx] = dtm
+                    .and_then(Dtm::to_stored)
+                    .ok_or_else(|| EndgameTableError::DistanceTooLong(table.name().to_string()))?;
+                for predecessor in table.predecessors(idx) {
+                    if remaining[predecessor] == ILLEGAL || table.values[predecessor] != 0 {
+                        continue;
+                    }
+                    if !is_win {
+                        schedule(&mut queue, plies as u8 + 1, predecessor);
+                    } else if remaining[predecessor] != NEVER_LOST {
+                        remaining[predecessor] -= 1;
+                        if remaining[predecessor] == 0 {
+                            schedule(&mut queue, conversion_loss[predecessor].max(plies as u8 + 1), predecessor);
+                        }
+                    }
+                }
+            }
+            plies += 1;
+        }
+        Ok(())
+    }
+
+    // Checks every position of a table against the values of its successors
+    pub fn verify(&self, name: &str) -> Result<(), EndgameTableError> {
+        let table = self.tables.get(name).ok_or_else(|| EndgameTableError::InvalidMaterial(name.to_string()))?;
+        for idx in 0..table.values.len() {
+            let mut board = match table.position(idx) {
+                Some(board) => board,
+                None => continue,
+            };
+            let moves = board.legal_moves();
+            let mut expected = if board.is_in_check() { Dtm::Loss(0) } else { Dtm::Draw };
+            for (i, move_) in moves.iter().enumerate() {
+                board.push(move_);
+                let dtm = self.lookup(&board);
+                board.pop();
+                let dtm = dtm.ok_or_else(|| EndgameTableError::InvalidMaterial(board.to_fen_string()))?.parent();
+                if i == 0 || dtm.rank() > expected.rank() {
+                    expected = dtm;
+                }
+            }
+            let actual = Dtm::from_stored(table.values[idx]);
+            if actual != expected {
+                return Err(EndgameTableError::Inconsistent(format!(
+                    "{}: expected {:?}, found {:?}",
+                    board.to_fen_string(),
+                    expected,
+                    actual
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lazy_static::lazy_static;
+    use test_case::test_case;
+
+    lazy_static! {
+        // KPvK depends on KQvK and KRvK
+        static ref TABLES: EndgameTables = {
+            let mut tables = EndgameTables::new();
+            tables.generate("KPvK").unwrap();
+            tables
+        };
+    }
+
+    #[test_case("KQvK", Ok(()))]
+    #[test_case("KNNvK", Ok(()))]
+    #[test_case("KvKQ", Err(()); "weaker side first")]
+    #[test_case("KQRvKR", Err(()); "too many pieces")]
+    #[test_case("KQvKX", Err(()); "unknown piece")]
+    #[test_case("QvK", Err(()); "missing king")]
+    fn test_material(name: &str, expected: Result<(), ()>) {
+        assert_eq!(EndgameTable::empty(name).map(|_| ()).map_err(|_| ()), expected);
+    }
+
+    #[test_case("KPvK", &["KQvK", "KRvK"])]
+    #[test_case("KRvKN", &["KRvK"])]
+    #[test_case("KPvKP", &["KBvKP", "KNvKP", "KPvK", "KQvK", "KQvKP", "KRvK", "KRvKP"])]
+    fn test_dependencies(name: &str, expected: &[&str]) {
+        assert_eq!(EndgameTables::dependencies(name), expected);
+    }
+
+    #[test]
+    fn test_generate() {
+        assert_eq!(TABLES.names(), vec!["KPvK", "KQvK", "KRvK"]);
+        assert_eq!(TABLES.max_pieces(), 3);
+        let longest_win = |name| TABLES.get(name).unwrap().values.iter().copied().max();
+        // mate in 10 and 16 moves
+        assert_eq!(longest_win("KQvK"), Some(19));
+        assert_eq!(longest_win("KRvK"), Some(31));
+    }
+
+    #[test_case("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1", Some(Dtm::Win(1)))]
+    #[test_case("k6Q/8/1K6/8/8/8/8/8 b - - 0 1", Some(Dtm::Loss(0)); "mated")]
+    #[test_case("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1", Some(Dtm::Draw); "stalemate")]
+    #[test_case("8/8/8/8/8/8/1kQ5/7K b - - 0 1", Some(Dtm::Draw); "queen capture")]
+    #[test_case("6q1/8/8/8/8/1k6/8/K7 b - - 0 1", Some(Dtm::Win(1)); "black queen")]
+    #[test_case("R6k/8/6K1/8/8/8/8/8 b - - 0 1", Some(Dtm::Loss(0)); "rook mate")]
+    #[test_case("6k1/8/6K1/8/8/8/8/R7 w - - 0 1", Some(Dtm::Win(1)); "rook mate in one")]
+    #[test_case("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1", Some(Dtm::Win(21)); "king in front of pawn")]
+    #[test_case("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1", Some(Dtm::Loss(24)); "king in front of pawn black to move")]
+    #[test_case("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1", Some(Dtm::Draw); "pawn stalemate")]
+    #[test_case("8/8/8/4k3/8/8/8/4K3 w - - 0 1", Some(Dtm::Draw); "bare kings")]
+    #[test_case("8/8/8/4k3/8/8/8/4K2R w K - 0 1", None; "castling rights")]
+    #[test_case("8/8/8/4k3/8/8/8/1RK1Q3 w - - 0 1", None; "too many pieces")]
+    #[test_case("8/8/8/4k3/8/8/8/1NK5 w - - 0 1", Some(Dtm::Draw); "insufficient material")]
+    #[test_case("8/8/8/4k3/8/8/8/1BKB4 w - - 0 1", None; "missing table")]
+    fn test_probe(fen: &str, expected: Option<Dtm>) {
+        let board = Board::parse_fen(fen).unwrap();
+        assert_eq!(TABLES.probe(&board), expected);
+    }
+
+    #[test_case(Dtm::Win(127), Some(127))]
+    #[test_case(Dtm::Loss(127), Some(-128))]
+    #[test_case(Dtm::Win(128), None; "win too long")]
+    #[test_case(Dtm::Loss(128), None; "loss too long")]
+    fn test_stored_values(dtm: Dtm, expected: Option<i8>) {
+        assert_eq!(dtm.to_stored(), expected);
+        if let Some(value) = expected {
+            assert_eq!(Dtm::from_stored(value), dtm);
+        }
+    }
+
+    #[test]
+    fn test_root_moves() {
+        let mut board = Board::parse_fen("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1").unwrap();
+        let moves: Vec<String> = TABLES.root_moves(&mut board).unwrap().iter().map(|m| m.to_uci_string()).collect();
+        assert_eq!(moves, vec!["g1g8"]);
+    }
+
+    #[test]
+    fn test_write_read() {
+        let table = TABLES.get("KRvK").unwrap();
+        let mut buffer = vec![];
+        table.write(&mut buffer).unwrap();
+        assert_eq!(&buffer[..10], b"CHEG\x01\x04KRvK");
+        let read = EndgameTable::read(buffer.as_slice()).unwrap();
+        assert_eq!(read.name(), "KRvK");
+        assert_eq!(read.values, table.values);
+        assert!(EndgameTable::read(&buffer[..buffer.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_verify() {
+        assert!(TABLES.verify("KQvK").is_ok());
+        assert!(TABLES.verify("KPvK").is_ok());
+
+        let mut buffer = vec![];
+        TABLES.get("KQvK").unwrap().write(&mut buffer).unwrap();
+        let mut table = EndgameTable::read(buffer.as_slice()).unwrap();
+        let board = Board::parse_fen("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1").unwrap();
+        let idx = table.board_index(&board, false);
+        table.values[idx] = Dtm::Win(3).to_stored().unwrap();
+        let mut tables = EndgameTables::new();
+        tables.insert(table);
+        assert!(matches!(tables.verify("KQvK"), Err(EndgameTableError::Inconsistent(_))));
+    }
+}
@@ -0,0 +1,375 @@
+use std::io::BufRead;
+
+use crate::board::Board;
+use crate::errors::{EpdParseError, FenField, FenParseError};
+use crate::Move;
+
+// opcodes whose operands are SAN moves played from the record's position
+const MOVE_OPCODES: [&str; 4] = ["am", "bm", "pm", "sm"];
+// opcodes whose operands are a line of SAN moves, each played from the position after the previous one
+const VARIATION_OPCODES: [&str; 2] = ["pv", "sv"];
+
+// (opcode, [(operand, is_quoted)]) before the operands are resolved against the position
+type RawOperation = (String, Vec<(String, bool)>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EpdOperand {
+    Move(Move),
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct EpdRecord {
+    pub board: Board,
+    pub operations: Vec<(String, Vec<EpdOperand>)>,
+}
+
+impl EpdRecord {
+    pub fn new(board: Board) -> Self {
+        EpdRecord {
+            board,
+            operations: vec![],
+        }
+    }
+
+    pub fn parse(epd: &str) -> Result<Self, EpdParseError> {
+        let mut rest = epd.trim();
+        let mut fields = vec![];
+        for field in [FenField::PiecePlacement, FenField::SideToMove, FenField::CastlingRights, FenField::EnPassantSquare] {
+            if rest.is_empty() {
+                return Err(EpdParseError::InvalidFen(FenParseError::MissingField(field)));
+            }
+            let (value, remainder) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            fields.push(value);
+            rest = remainder.trim_start();
+        }
+
+        let raw_operations = EpdRecord::parse_operations(rest)?;
+        let clock = |opcode: &str| {
+            raw_operations
+                .iter()
+                .find(|(o, _)| o == opcode)
+                .and_then(|(_, operands)| operands.first())
+                .map(|(text, _)| text.as_str())
+        };
+        let half_move_clock = match clock("hmvc") {
+            Some(text) => text.parse().map_err(|_| EpdParseError::InvalidOperand {
+                opcode: "hmvc".to_string(),
+                operand: text.to_string(),
+            })?,
+            None => 0,
+        };
+        let full_move_number = match clock("fmvn") {
+            Some(text) => text.parse().map_err(|_| EpdParseError::InvalidOperand {
+                opcode: "fmvn".to_string(),
+                operand: text.to_string(),
+            })?,
+            None => 1,
+        };
+        let board = Board::from_fen_fields(fields[0], fields[1], fields[2], fields[3], half_move_clock, full_move_number)
+            .map_err(EpdParseError::InvalidFen)?;
+
+        let mut record = EpdRecord::new(board);
+        for (opcode, raw_operands) in raw_operations {
+            let operands = record.resolve_operands(&opcode, raw_operands)?;
+            record.operations.push((opcode, operands));
+        }
+        Ok(record)
+    }
+
+    fn parse_operations(text: &str) -> Result<Vec<RawOperation>, EpdParseError> {
+        let chars = text.chars().collect::<Vec<char>>();
+        let mut pos = 0;
+        let mut operations = vec![];
+        loop {
+            while pos < chars.len() && chars[pos].is_whitespace() {
+                pos += 1;
+            }
+            if pos >= chars.len() {
+                return Ok(operations);
+            }
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            let opcode: String = chars[start..pos].iter().collect();
+            if !opcode.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                return Err(EpdParseError::InvalidOperation(chars[start..].iter().collect()));
+            }
+            let mut operands = vec![];
+            loop {
+                while pos < chars.len() && chars[pos].is_whitespace() {
+                    pos += 1;
+                }
+                match chars.get(pos) {
+                    // be lenient about a missing ';' after the last operation
+                    None => break,
+                    Some(';') => {
+                        pos += 1;
+                        break;
+                    }
+                    Some('"') => {
+                        // as in PGN, a quote or backslash inside a string is escaped with a backslash
+                        let mut text = String::new();
+                        let mut end = pos + 1;
+                        while end < chars.len() && chars[end] != '"' {
+                            if chars[end] == '\\' && matches!(chars.get(end + 1), Some('"' | '\\')) {
+                                end += 1;
+                            }
+                            text.push(chars[end]);
+                            end += 1;
+                        }
+                        if end >= chars.len() {
+                            return Err(EpdParseError::InvalidOperation(format!("{} {}", opcode, chars[pos..].iter().collect::<String>())));
+                        }
+                        operands.push((text, true));
+                        pos = end + 1;
+                    }
+                    Some(_) => {
+                        let start = pos;
+                        while pos < chars.len() && !chars[pos].is_whitespace() && chars[pos] != ';' {
+                            pos += 1;
+                        }
+                        operands.push((chars[start..pos].iter().collect(), false));
+                    }
+                }
+            }
+            if operations.iter().any(|(o, _)| *o == opcode) {
+                return Err(EpdParseError::DuplicateOpcode(opcode));
+            }
+            operations.push((opcode, operands));
+        }
+    }
+
+    fn resolve_operands(&self, opcode: &str, raw_operands: Vec<(String, bool)>) -> Result<Vec<EpdOperand>, EpdParseError> {
+        let invalid_move = |e| EpdParseError::InvalidMove {
+            opcode: opcode.to_string(),
+            error: e,
+        };
+        if MOVE_OPCODES.contains(&opcode) {
+            return raw_operands
+                .iter()
+                .map(|(san, _)| self.board.parse_san(san).map(EpdOperand::Move).map_err(invalid_move))
+                .collect();
+        }
+        if VARIATION_OPCODES.contains(&opcode) {
+            let mut board = self.board.clone();
+            return raw_operands
+                .iter()
+                .map(|(san, _)| board.push_san(san).map(EpdOperand::Move).map_err(invalid_move))
+                .collect();
+        }
+        Ok(raw_operands
+            .into_iter()
+            .map(|(text, is_quoted)| {
+                if is_quoted {
+                    EpdOperand::String(text)
+                } else if let Ok(i) = text.parse::<i64>() {
+                    EpdOperand::Integer(i)
+                } else if let Ok(f) = text.parse::<f64>() {
+                    EpdOperand::Float(f)
+                } else {
+                    EpdOperand::String(text)
+                }
+            })
+            .collect())
+    }
+
+    pub fn operation(&self, opcode: &str) -> Option<&[EpdOperand]> {
+        self.operations
+            .iter()
+            .find(|(o, _)| o == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    pub fn set_operation(&mut self, opcode: &str, operands: Vec<EpdOperand>) {
+        match self.operations.iter_mut().find(|(o, _)| o == opcode) {
+            Some(operation) => operation.1 = operands,
+            None => self.operations.push((opcode.to_string(), operands)),
+        }
+    }
+
+    pub fn moves(&self, opcode: &str) -> Vec<Move> {
+        self.operation(opcode)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|operand| match operand {
+                EpdOperand::Move(m) => Some(*m),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn integer(&self, opcode: &str) -> Option<i64> {
+        match self.operation(opcode)?.first()? {
+            EpdOperand::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn string(&self, opcode: &str) -> Option<&str> {
+        match self.operation(opcode)?.first()? {
+            EpdOperand::String(s) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn to_epd_string(&self) -> String {
+        let fen = self.board.to_fen_string();
+        let mut epd = fen.split(' ').take(4).collect::<Vec<&str>>().join(" ");
+        for (opcode, operands) in self.operations.iter() {
+            epd.push(' ');
+            epd.push_str(opcode);
+            let mut board = self.board.clone();
+            for operand in operands.iter() {
+                let text = match operand {
+                    EpdOperand::Move(m) => {
+                        let san = m.pgn_spec(&board);
+                        if VARIATION_OPCODES.contains(&opcode.as_str()) {
+                            board.push(m);
+                        }
+                        san
+                    }
+                    EpdOperand::Integer(i) => i.to_string(),
+                    // keep the decimal point, so that the operand is read back as a float
+                    EpdOperand::Float(f) => format!("{:?}", f),
+                    EpdOperand::String(s) => format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")),
+                };
+                epd.push(' ');
+                epd.push_str(&text);
+            }
+            epd.push(';');
+        }
+        epd
+    }
+}
+
+pub struct EpdReader<R: BufRead> {
+    input: R,
+}
+
+impl<R: BufRead> EpdReader<R> {
+    pub fn new(input: R) -> Self {
+        EpdReader { input }
+    }
+}
+
+impl<R: BufRead> Iterator for EpdReader<R> {
+    type Item = Result<EpdRecord, EpdParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut line = String::new();
+            match self.input.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => return Some(Err(EpdParseError::Io(e))),
+            }
+            // skip blank lines and '#' comments between records
+            let line = line.trim_start_matches('\u{feff}').trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                return Some(EpdRecord::parse(line));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::SanParseError;
+    use crate::perft::perft;
+    use std::io::Cursor;
+    use test_case::test_case;
+
+    const PERFT_SUITE: &str = "\
+# standard perft positions, depths kept shallow for debug builds
+rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - D1 20; D2 400; D3 8902;
+r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - D1 48; D2 2039; D3 97862;
+
+8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - D1 14; D2 191; D3 2812;
+r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - D1 6; D2 264; D3 9467;
+rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - D1 44; D2 1486; D3 62379;
+";
+
+    #[test]
+    fn test_parse_test_position() {
+        let record = EpdRecord::parse(r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";"#).unwrap();
+        assert_eq!(record.board.to_fen_string(), "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1");
+        let best_moves = record.moves("bm");
+        assert_eq!(best_moves.len(), 1);
+        assert_eq!(best_moves[0].to_uci_string(), "g3g6");
+        assert_eq!(record.string("id"), Some("WAC.001"));
+        assert!(record.operation("am").is_none());
+    }
+
+    #[test]
+    fn test_parse_operations() {
+        let epd = r#"r1bqk1nr/pppp1ppp/2n5/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - am Ng5 d3; acd 12; ce -35; c0 "Italian; main line"; pv O-O Nf6 d3; hmvc 4; fmvn 4;"#;
+        let record = EpdRecord::parse(epd).unwrap();
+        assert_eq!(record.board.to_fen_string(), "r1bqk1nr/pppp1ppp/2n5/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4");
+        let avoid_moves = record.moves("am").iter().map(|m| m.to_uci_string()).collect::<Vec<String>>();
+        assert_eq!(avoid_moves, vec!["f3g5", "d2d3"]);
+        assert_eq!(record.integer("acd"), Some(12));
+        assert_eq!(record.integer("ce"), Some(-35));
+        assert_eq!(record.string("c0"), Some("Italian; main line"));
+        let pv = record.moves("pv").iter().map(|m| m.to_uci_string()).collect::<Vec<String>>();
+        assert_eq!(pv, vec!["e1g1", "g8f6", "d2d3"]);
+        assert_eq!(record.to_epd_string(), epd);
+    }
+
+    #[test]
+    fn test_write_record() {
+        let mut record = EpdRecord::new(Board::starting_position());
+        let move_ = record.board.parse_san("e4").unwrap();
+        record.set_operation("bm", vec![EpdOperand::Move(move_)]);
+        record.set_operation("id", vec![EpdOperand::String("start".to_string())]);
+        record.set_operation("acs", vec![EpdOperand::Float(1.5)]);
+        record.set_operation("id", vec![EpdOperand::String("opening".to_string())]);
+        assert_eq!(
+            record.to_epd_string(),
+            r#"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm e4; id "opening"; acs 1.5;"#
+        );
+    }
+
+    #[test]
+    fn test_operands_round_trip() {
+        let mut record = EpdRecord::new(Board::starting_position());
+        record.set_operation("acs", vec![EpdOperand::Float(1.0), EpdOperand::Float(-0.25), EpdOperand::Integer(3)]);
+        record.set_operation("c0", vec![EpdOperand::String(r#"the "Ruy Lopez" \ Spanish"#.to_string())]);
+        let epd = record.to_epd_string();
+        assert_eq!(
+            epd,
+            r#"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - acs 1.0 -0.25 3; c0 "the \"Ruy Lopez\" \\ Spanish";"#
+        );
+        let parsed = EpdRecord::parse(&epd).unwrap();
+        assert_eq!(parsed.operations, record.operations);
+        // a backslash that escapes nothing is kept
+        assert_eq!(EpdRecord::parse(r#"8/8/8/8/8/8/8/K1k5 w - - id "C:\suite";"#).unwrap().string("id"), Some(r#"C:\suite"#));
+    }
+
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w", EpdParseError::InvalidFen(FenParseError::MissingField(FenField::CastlingRights)) ; "missing field")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm Nf4;", EpdParseError::InvalidMove { opcode: "bm".to_string(), error: SanParseError::IllegalMove("Nf4".to_string()) } ; "illegal best move")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - id \"unterminated;", EpdParseError::InvalidOperation("id \"unterminated;".to_string()) ; "unterminated string")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 5d 3;", EpdParseError::InvalidOperation("5d 3;".to_string()) ; "bad opcode")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - id \"a\"; bm e4; id \"b\";", EpdParseError::DuplicateOpcode("id".to_string()) ; "duplicate opcode")]
+    #[test_case("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - hmvc x;", EpdParseError::InvalidOperand { opcode: "hmvc".to_string(), operand: "x".to_string() } ; "bad half move clock")]
+    fn test_parse_errors(epd: &str, expected_error: EpdParseError) {
+        assert_eq!(EpdRecord::parse(epd).unwrap_err().to_string(), expected_error.to_string());
+    }
+
+    #[test]
+    fn test_perft_suite() {
+        let records = EpdReader::new(Cursor::new(PERFT_SUITE)).collect::<Result<Vec<EpdRecord>, EpdParseError>>().unwrap();
+        assert_eq!(records.len(), 5);
+        for record in records.iter() {
+            for depth in 1u8..=3 {
+                let mut board = record.board.clone();
+                let expected = record.integer(&format!("D{}", depth)).unwrap() as u64;
+                assert_eq!(perft(&mut board, depth), expected, "{}", record.to_epd_string());
+            }
+        }
+    }
+}
@@ -1,8 +1,84 @@
+use std::fmt;
+use std::fmt::Formatter;
+
+use crate::square::Square;
+use crate::Side;
+
 #[derive(Debug)]
 pub struct IllegalMoveError;
 
-#[derive(Debug)]
-pub struct FenParseError;
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FenField {
+    PiecePlacement,
+    SideToMove,
+    CastlingRights,
+    EnPassantSquare,
+    HalfMoveClock,
+    FullMoveNumber,
+}
+
+impl fmt::Display for FenField {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FenField::PiecePlacement => "piece placement",
+            FenField::SideToMove => "side to move",
+            FenField::CastlingRights => "castling rights",
+            FenField::EnPassantSquare => "en passant square",
+            FenField::HalfMoveClock => "half move clock",
+            FenField::FullMoveNumber => "full move number",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum IllegalPositionError {
+    MissingKing(Side),
+    TooManyKings(Side),
+    TooManyPawns(Side),
+    TooManyPieces(Side),
+    PawnOnBackRank(Square),
+    OpponentInCheck,
+    InvalidCastlingRights(Side),
+    InvalidEnPassantSquare(Square),
+}
+
+impl fmt::Display for IllegalPositionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            IllegalPositionError::MissingKing(side) => write!(f, "{:?} has no king", side),
+            IllegalPositionError::TooManyKings(side) => write!(f, "{:?} has more than one king", side),
+            IllegalPositionError::TooManyPawns(side) => write!(f, "{:?} has more than 8 pawns", side),
+            IllegalPositionError::TooManyPieces(side) => write!(f, "{:?} has more than 16 pieces", side),
+            IllegalPositionError::PawnOnBackRank(square) => write!(f, "pawn on back rank square {}", square),
+            IllegalPositionError::OpponentInCheck => write!(f, "the side not to move is in check"),
+            IllegalPositionError::InvalidCastlingRights(side) => {
+                write!(f, "{:?} castling rights without king and rook on their original squares", side)
+            }
+            IllegalPositionError::InvalidEnPassantSquare(square) => {
+                write!(f, "en passant square {} does not follow a pawn double push", square)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum FenParseError {
+    MissingField(FenField),
+    TooManyFields(usize),
+    InvalidField { field: FenField, reason: String },
+    IllegalPosition(IllegalPositionError),
+}
+
+impl fmt::Display for FenParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FenParseError::MissingField(field) => write!(f, "missing {} field", field),
+            FenParseError::TooManyFields(n) => write!(f, "expected 6 fields, found {}", n),
+            FenParseError::InvalidField { field, reason } => write!(f, "invalid {} field: {}", field, reason),
+            FenParseError::IllegalPosition(e) => write!(f, "illegal position: {}", e),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct FenCharParseError;
@@ -15,3 +91,64 @@ pub struct MagicTableMaxAttemptsExceededError;
 
 #[derive(Debug)]
 pub struct HashCollisionError;
+
+#[derive(Debug)]
+pub enum PgnParseError {
+    Io(std::io::Error),
+    InvalidTag(String),
+    InvalidFen(String),
+    IllegalMove(String),
+    UnexpectedToken(String),
+    UnexpectedEof,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SanParseError {
+    InvalidSyntax(String),
+    IllegalMove(String),
+    AmbiguousMove(String),
+}
+
+#[derive(Debug)]
+pub enum EpdParseError {
+    Io(std::io::Error),
+    InvalidFen(FenParseError),
+    InvalidOperation(String),
+    DuplicateOpcode(String),
+    InvalidOperand { opcode: String, operand: String },
+    InvalidMove { opcode: String, error: SanParseError },
+}
+
+impl fmt::Display for EpdParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EpdParseError::Io(e) => write!(f, "{}", e),
+            EpdParseError::InvalidFen(e) => write!(f, "{}", e),
+            EpdParseError::InvalidOperation(text) => write!(f, "invalid operation: {}", text),
+            EpdParseError::DuplicateOpcode(opcode) => write!(f, "duplicate opcode: {}", opcode),
+            EpdParseError::InvalidOperand { opcode, operand } => write!(f, "invalid operand for {}: {}", opcode, operand),
+            EpdParseError::InvalidMove { opcode, error } => write!(f, "invalid move for {}: {:?}", opcode, error),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EndgameTableError {
+    Io(std::io::Error),
+    InvalidMaterial(String),
+    InvalidFile(String),
+    Inconsistent(String),
+    DistanceTooLong(String),
+}
+
+impl fmt::Display for EndgameTableError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EndgameTableError::Io(e) => write!(f, "{}", e),
+            EndgameTableError::InvalidMaterial(name) => write!(f, "invalid material signature: {}", name),
+            EndgameTableError::InvalidFile(reason) => write!(f, "invalid endgame table file: {}", reason),
+            EndgameTableError::Inconsistent(reason) => write!(f, "inconsistent endgame table: {}", reason),
+            EndgameTableError::DistanceTooLong(name) => write!(f, "distance to mate too long for the table format: {}", name),
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use string_builder::Builder;
+
+use crate::board::{Board, Outcome};
+use crate::{Move, Side};
+
+const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+const MAX_LINE_LENGTH: usize = 79;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    InsufficientMaterial,
+    FivefoldRepetition,
+    SeventyFiveMoveRule,
+    ThreefoldRepetition,
+    FiftyMoveRule,
+    Agreement,
+    Resignation,
+    TimeForfeit,
+    Adjudication,
+}
+
+impl Termination {
+    fn description(self) -> &'static str {
+        match self {
+            Termination::Checkmate => "checkmate",
+            Termination::Stalemate => "stalemate",
+            Termination::InsufficientMaterial => "insufficient material",
+            Termination::FivefoldRepetition => "fivefold repetition",
+            Termination::SeventyFiveMoveRule => "seventy-five move rule",
+            Termination::ThreefoldRepetition => "threefold repetition",
+            Termination::FiftyMoveRule => "fifty move rule",
+            Termination::Agreement => "agreement",
+            Termination::Resignation => "resignation",
+            Termination::TimeForfeit => "time forfeit",
+            Termination::Adjudication => "adjudication",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GameResult {
+    WhiteWins(Termination),
+    BlackWins(Termination),
+    Draw(Termination),
+    Ongoing,
+}
+
+impl GameResult {
+    pub fn win_for(side: Side, termination: Termination) -> Self {
+        match side {
+            Side::White => GameResult::WhiteWins(termination),
+            Side::Black => GameResult::BlackWins(termination),
+        }
+    }
+
+    pub fn pgn_spec(&self) -> &'static str {
+        match self {
+            GameResult::WhiteWins(_) => "1-0",
+            GameResult::BlackWins(_) => "0-1",
+            GameResult::Draw(_) => "1/2-1/2",
+            GameResult::Ongoing => "*",
+        }
+    }
+
+    pub fn termination(&self) -> Option<Termination> {
+        match self {
+            GameResult::WhiteWins(t) | GameResult::BlackWins(t) | GameResult::Draw(t) => Some(*t),
+            GameResult::Ongoing => None,
+        }
+    }
+
+    fn description(&self) -> Option<String> {
+        match self {
+            GameResult::WhiteWins(t) => Some(format!("White wins by {}", t.description())),
+            GameResult::BlackWins(t) => Some(format!("Black wins by {}", t.description())),
+            GameResult::Draw(t) => Some(format!("Draw by {}", t.description())),
+            GameResult::Ongoing => None,
+        }
+    }
+}
+
+impl From<Outcome> for GameResult {
+    fn from(outcome: Outcome) -> Self {
+        match outcome {
+            Outcome::Checkmate { winner } => GameResult::win_for(winner, Termination::Checkmate),
+            Outcome::Stalemate => GameResult::Draw(Termination::Stalemate),
+            Outcome::InsufficientMaterial => GameResult::Draw(Termination::InsufficientMaterial),
+            Outcome::FivefoldRepetition => GameResult::Draw(Termination::FivefoldRepetition),
+            Outcome::SeventyFiveMoveRule => GameResult::Draw(Termination::SeventyFiveMoveRule),
+            Outcome::ThreefoldRepetition => GameResult::Draw(Termination::ThreefoldRepetition),
+            Outcome::FiftyMoveRule => GameResult::Draw(Termination::FiftyMoveRule),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Game {
+    headers: Vec<(String, String)>,
+    starting_position: Board,
+    board: Board,
+    moves: Vec<Move>,
+    result: GameResult,
+}
+
+impl Game {
+    pub fn new() -> Self {
+        Game::from_position(Board::starting_position())
+    }
+
+    pub fn from_position(board: Board) -> Self {
+        let headers = [
+            ("Event", "?"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Round", "?"),
+            ("White", "?"),
+            ("Black", "?"),
+        ];
+        Game {
+            headers: headers
+                .iter()
+                .map(|&(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+            starting_position: board.clone(),
+            board,
+            moves: vec![],
+            result: GameResult::Ongoing,
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        if name == "Result" {
+            return Some(self.result.pgn_spec());
+        }
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        match self.headers.iter_mut().find(|(n, _)| n == name) {
+            Some(header) => header.1 = value.to_string(),
+            None => self.headers.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn starting_position(&self) -> &Board {
+        &self.starting_position
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    pub fn result(&self) -> GameResult {
+        self.result
+    }
+
+    pub fn set_result(&mut self, result: GameResult) {
+        self.result = result;
+    }
+
+    pub fn push(&mut self, move_: Move) {
+        self.board.push(&move_);
+        self.moves.push(move_);
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let mut sb = Builder::default();
+        for &name in SEVEN_TAG_ROSTER.iter() {
+            sb.append(Game::tag_pair(name, self.header(name).unwrap_or("?")));
+        }
+        let starting_fen = self.starting_position.to_fen_string();
+        if starting_fen != STARTING_FEN {
+            sb.append(Game::tag_pair("SetUp", "1"));
+            sb.append(Game::tag_pair("FEN", &starting_fen));
+        }
+        for (name, value) in self.headers.iter() {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) && name != "SetUp" && name != "FEN" {
+                sb.append(Game::tag_pair(name, value));
+            }
+        }
+        sb.append('\n');
+
+        let mut tokens: Vec<String> = vec![];
+        let mut board = self.starting_position.clone();
+        for (i, move_) in self.moves.iter().enumerate() {
+            if board.side_to_move() == Side::White {
+                tokens.push(format!("{}.", board.full_move_number()));
+            } else if i == 0 {
+                tokens.push(format!("{}...", board.full_move_number()));
+            }
+            tokens.push(move_.pgn_spec(&board));
+            board.push(move_);
+        }
+        if let Some(description) = self.result.description() {
+            tokens.push(format!("{{{}}}", description));
+        }
+        tokens.push(self.result.pgn_spec().to_string());
+
+        let mut line_length = 0;
+        for token in tokens.iter() {
+            if line_length > 0 {
+                if line_length + 1 + token.len() > MAX_LINE_LENGTH {
+                    sb.append('\n');
+                    line_length = 0;
+                } else {
+                    sb.append(' ');
+                    line_length += 1;
+                }
+            }
+            sb.append(token.as_str());
+            line_length += token.len();
+        }
+        sb.append("\n\n");
+
+        sb.string().unwrap()
+    }
+
+    fn tag_pair(name: &str, value: &str) -> String {
+        let escaped_value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        format!("[{} \"{}\"]\n", name, escaped_value)
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::PgnReader;
+    use crate::Piece;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_empty_game_pgn() {
+        let game = Game::new();
+        let expected = "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n\
+            [White \"?\"]\n[Black \"?\"]\n[Result \"*\"]\n\n*\n\n";
+        assert_eq!(game.to_pgn(), expected);
+    }
+
+    #[test]
+    fn test_fools_mate_pgn() {
+        let mut game = Game::new();
+        game.set_header("Event", "Fool's \"mate\"");
+        game.set_header("Annotator", "Chessica");
+        for san in ["f3", "e5", "g4", "Qh4"] {
+            let move_ = game.board().parse_san(san).unwrap();
+            game.push(move_);
+        }
+        game.set_result(game.board().outcome().unwrap().into());
+        let expected = "[Event \"Fool's \\\"mate\\\"\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n\
+            [Round \"?\"]\n[White \"?\"]\n[Black \"?\"]\n[Result \"0-1\"]\n[Annotator \"Chessica\"]\n\n\
+            1. f3 e5 2. g4 Qh4# {Black wins by checkmate} 0-1\n\n";
+        assert_eq!(game.to_pgn(), expected);
+    }
+
+    #[test]
+    fn test_non_standard_starting_position() {
+        let board = Board::parse_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 12").unwrap();
+        let mut game = Game::from_position(board);
+        let move_ = game.board().parse_san("Kd7").unwrap();
+        game.push(move_);
+        game.set_result(GameResult::Draw(Termination::Agreement));
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 12\"]\n"));
+        assert!(pgn.ends_with("\n\n12... Kd7 {Draw by agreement} 1/2-1/2\n\n"));
+    }
+
+    #[test]
+    fn test_pgn_round_trip() {
+        let mut game = Game::new();
+        let mut board = Board::starting_position();
+        for _ in 0..60 {
+            // shuffle knights back and forth so the game is long enough to need wrapping
+            let move_ = board.legal_moves().into_iter().find(|m| m.piece() == Piece::Knight).unwrap();
+            board.push(&move_);
+            game.push(move_);
+        }
+        game.set_result(GameResult::Draw(Termination::ThreefoldRepetition));
+        let pgn = game.to_pgn();
+        assert!(pgn.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+
+        let parsed = PgnReader::new(Cursor::new(pgn)).next().unwrap().unwrap();
+        assert_eq!(parsed.moves, game.moves());
+        assert_eq!(parsed.result, "1/2-1/2");
+        assert_eq!(parsed.comments, vec![(60, "Draw by threefold repetition".to_string())]);
+        assert_eq!(parsed.board.to_fen_string(), game.board().to_fen_string());
+    }
+}
@@ -7,12 +7,18 @@ use crate::board::Board;
 use crate::errors::FenCharParseError;
 
 pub mod board;
+pub mod book;
+pub mod endgame;
+pub mod epd;
+pub mod errors;
+pub mod game;
 pub mod magic;
 pub mod perft;
+pub mod pgn;
 pub mod square;
+pub mod syzygy;
 
 mod bitboard;
-mod errors;
 mod masks;
 mod pst;
 mod zobrist;
@@ -25,6 +31,13 @@ pub enum Side {
 }
 
 impl Side {
+    pub fn opposite(&self) -> Side {
+        match *self {
+            Side::White => Side::Black,
+            Side::Black => Side::White
+        }
+    }
+
     pub fn loss_score(&self) -> i32 {
         match *self {
             Side::White => -50_000,
@@ -87,6 +100,17 @@ impl Piece {
         }
     }
 
+    fn parse_pgn_char(c: u8) -> Option<Piece> {
+        match c {
+            b'N' => Some(Piece::Knight),
+            b'B' => Some(Piece::Bishop),
+            b'R' => Some(Piece::Rook),
+            b'Q' => Some(Piece::Queen),
+            b'K' => Some(Piece::King),
+            _ => None,
+        }
+    }
+
     pub fn pgn_spec(self) -> String {
         match self {
             Piece::Pawn => "",
@@ -128,6 +152,12 @@ pub struct EnPassantCaptureMove {
     _captured_pawn: Square,
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct CastlingMove {
+    _king_from: Square,
+    _rook_from: Square,
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct PromotionMove {
     _from: Square,
@@ -217,6 +247,40 @@ impl EnPassantCaptureMove {
     }
 }
 
+impl CastlingMove {
+    pub fn new(king_from: Square, rook_from: Square) -> Self {
+        CastlingMove {
+            _king_from: king_from,
+            _rook_from: rook_from,
+        }
+    }
+
+    pub fn side(&self) -> Side {
+        if self._king_from.rank() == 0 { Side::White } else { Side::Black }
+    }
+
+    pub fn is_short(&self) -> bool {
+        self._rook_from.file() > self._king_from.file()
+    }
+
+    pub fn king_from(&self) -> Square {
+        self._king_from
+    }
+
+    pub fn rook_from(&self) -> Square {
+        self._rook_from
+    }
+
+    // the king and rook always end up on the same squares as in standard chess, wherever they started
+    pub fn king_to(&self) -> Square {
+        Square::from_coords(self._king_from.rank(), if self.is_short() { 6 } else { 2 })
+    }
+
+    pub fn rook_to(&self) -> Square {
+        Square::from_coords(self._king_from.rank(), if self.is_short() { 5 } else { 3 })
+    }
+}
+
 impl PromotionMove {
     pub fn new(
         from: Square,
@@ -275,8 +339,8 @@ impl PromotionMove {
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Move {
     Regular(RegularMove),
-    ShortCastling(Side),
-    LongCastling(Side),
+    ShortCastling(CastlingMove),
+    LongCastling(CastlingMove),
     EnPassantCapture(EnPassantCaptureMove),
     Promotion(PromotionMove)
 }
@@ -286,12 +350,12 @@ impl Move {
         Move::Regular(RegularMove::new(piece, from, to, captured_piece))
     }
 
-    pub fn short_castling(side: Side) -> Self {
-        Move::ShortCastling(side)
+    pub fn short_castling(king_from: Square, rook_from: Square) -> Self {
+        Move::ShortCastling(CastlingMove::new(king_from, rook_from))
     }
 
-    pub fn long_castling(side: Side) -> Self {
-        Move::LongCastling(side)
+    pub fn long_castling(king_from: Square, rook_from: Square) -> Self {
+        Move::LongCastling(CastlingMove::new(king_from, rook_from))
     }
 
     pub fn promotion(
@@ -335,8 +399,7 @@ impl Move {
     pub fn from(&self) -> Square {
         match self {
             Move::Regular(m) => m.from(),
-            Move::ShortCastling(side) => if *side == Side::White { sq!(e1) } else { sq!(e8 )},
-            Move::LongCastling(side) => if *side == Side::White { sq!(e1) } else { sq!(e8 )},
+            Move::ShortCastling(m) | Move::LongCastling(m) => m.king_from(),
             Move::EnPassantCapture(m) => m.from(),
             Move::Promotion(m) => m.from()
         }
@@ -345,8 +408,7 @@ impl Move {
     pub fn to(&self) -> Square {
         match self {
             Move::Regular(m) => m.to(),
-            Move::ShortCastling(side) => if *side == Side::White { sq!(g1) } else { sq!(g8 )},
-            Move::LongCastling(side) => if *side == Side::White { sq!(c1) } else { sq!(c8 )},
+            Move::ShortCastling(m) | Move::LongCastling(m) => m.king_to(),
             Move::EnPassantCapture(m) => m.to(),
             Move::Promotion(m) => m.to()
         }
@@ -382,6 +444,13 @@ impl Move {
         }
     }
 
+    pub fn promotion_piece(&self) -> Option<Piece> {
+        match self {
+            Move::Promotion(m) => Some(m.promotion_piece()),
+            _ => None
+        }
+    }
+
     pub fn is_pawn_involved(&self) -> bool {
         match self {
             Move::Regular(m) => m.is_pawn_involved(),
@@ -401,12 +470,7 @@ impl Move {
     pub fn to_uci_string(&self) -> String {
         match self {
             Move::Regular(m) => format!("{}{}", m.from(), m.to()),
-            Move::ShortCastling(side) => {
-                String::from(if *side == Side::White { "e1g1" } else { "e8g8" })
-            }
-            Move::LongCastling(side) => {
-                String::from(if *side == Side::White { "e1c1" } else { "e8c8" })
-            }
+            Move::ShortCastling(m) | Move::LongCastling(m) => format!("{}{}", m.king_from(), m.king_to()),
             Move::EnPassantCapture(ep) => format!("{}{}", ep.from(), ep.to()),
             Move::Promotion(m) => format!(
                 "{}{}{}",
@@ -416,4 +480,65 @@ impl Move {
             )
         }
     }
+
+    // in Chess960 castling is encoded as the king capturing its own rook, since the king's
+    // destination alone may be ambiguous with a regular king move
+    pub fn to_uci_string_chess960(&self) -> String {
+        match self {
+            Move::ShortCastling(m) | Move::LongCastling(m) => format!("{}{}", m.king_from(), m.rook_from()),
+            _ => self.to_uci_string()
+        }
+    }
+}
+
+const PACKED_REGULAR: u16 = 0;
+const PACKED_EN_PASSANT: u16 = 1;
+const PACKED_SHORT_CASTLING: u16 = 2;
+const PACKED_LONG_CASTLING: u16 = 3;
+const PACKED_PROMOTION: u16 = 4;
+
+// a move in 16 bits: the from square, the to square and a 4 bit kind of move, with the
+// promotion piece folded into the kind; castling is packed as the king capturing its own rook
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct PackedMove(pub u16);
+
+impl PackedMove {
+    pub fn pack(move_: &Move) -> Self {
+        let (from, to, kind) = match move_ {
+            Move::Regular(m) => (m.from(), m.to(), PACKED_REGULAR),
+            Move::EnPassantCapture(m) => (m.from(), m.to(), PACKED_EN_PASSANT),
+            Move::ShortCastling(m) => (m.king_from(), m.rook_from(), PACKED_SHORT_CASTLING),
+            Move::LongCastling(m) => (m.king_from(), m.rook_from(), PACKED_LONG_CASTLING),
+            Move::Promotion(m) => {
+                (m.from(), m.to(), PACKED_PROMOTION + m.promotion_piece() as u16 - Piece::Knight as u16)
+            }
+        };
+        PackedMove(from.ordinal as u16 | (to.ordinal as u16) << 6 | kind << 12)
+    }
+
+    pub fn from(self) -> Square {
+        Square::from_ordinal((self.0 & 0x3f) as u8)
+    }
+
+    pub fn to(self) -> Square {
+        Square::from_ordinal((self.0 >> 6 & 0x3f) as u8)
+    }
+
+    // the full move with the pieces on the board; it is only pseudo-legal if Board::is_pseudo_legal says so
+    pub fn unpack(self, board: &Board) -> Option<Move> {
+        let (from, to) = (self.from(), self.to());
+        let piece = board.get_piece(board.side_to_move(), from)?;
+        let captured_piece = board.get_piece(board.side_to_not_move(), to);
+        match self.0 >> 12 {
+            PACKED_REGULAR => Some(Move::regular(piece, from, to, captured_piece)),
+            PACKED_EN_PASSANT => Some(Move::en_passant(from, to, Square::from_coords(from.rank(), to.file()))),
+            PACKED_SHORT_CASTLING => Some(Move::short_castling(from, to)),
+            PACKED_LONG_CASTLING => Some(Move::long_castling(from, to)),
+            kind if kind < PACKED_PROMOTION + 4 => {
+                let promotion_piece = Piece::from((kind - PACKED_PROMOTION) as u8 + Piece::Knight as u8);
+                Some(Move::promotion(from, to, promotion_piece, captured_piece))
+            },
+            _ => None
+        }
+    }
 }
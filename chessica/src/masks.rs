@@ -10,6 +10,8 @@ pub const FILE: [u64; 8] = [
     0x1010101010101010, 0x2020202020202020, 0x4040404040404040, 0x8080808080808080
 ];
 
+pub const LIGHT_SQUARES: u64 = 0x55aa55aa55aa55aa;
+
 #[rustfmt::skip]
 pub const WHITE_PASSED_PAWN_ZONE: [u64; 64] = [
     0x0000000000000000, 0x0000000000000000, 0x0000000000000000, 0x0000000000000000,
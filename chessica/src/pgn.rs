@@ -0,0 +1,467 @@
+use std::io::BufRead;
+
+use crate::board::Board;
+use crate::errors::PgnParseError;
+use crate::Move;
+
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    pub headers: Vec<(String, String)>,
+    pub starting_position: Board,
+    pub moves: Vec<Move>,
+    // (ply, text): the comment follows the first `ply` moves of the game
+    pub comments: Vec<(usize, String)>,
+    // (ply, nag): the NAG annotates move number `ply` (1-based)
+    pub nags: Vec<(usize, u8)>,
+    pub result: String,
+    pub board: Board,
+}
+
+impl PgnGame {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Token {
+    Tag(String, String),
+    MoveNumber,
+    San(String),
+    Nag(u8),
+    Comment(String),
+    VariationStart,
+    VariationEnd,
+    Result(String),
+}
+
+pub struct PgnReader<R: BufRead> {
+    input: R,
+    line: Vec<char>,
+    pos: usize,
+    peeked: Option<Token>,
+    eof: bool,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(input: R) -> Self {
+        PgnReader {
+            input,
+            line: vec![],
+            pos: 0,
+            peeked: None,
+            eof: false,
+        }
+    }
+
+    fn next_line(&mut self) -> Result<bool, PgnParseError> {
+        loop {
+            let mut buf = String::new();
+            if self.input.read_line(&mut buf).map_err(PgnParseError::Io)? == 0 {
+                self.eof = true;
+                return Ok(false);
+            }
+            // '%' in the first column escapes the rest of the line
+            if buf.starts_with('%') {
+                continue;
+            }
+            self.line = buf.trim_start_matches('\u{feff}').chars().collect();
+            self.pos = 0;
+            return Ok(true);
+        }
+    }
+
+    fn peek_char(&mut self) -> Result<Option<char>, PgnParseError> {
+        while self.pos >= self.line.len() {
+            if self.eof || !self.next_line()? {
+                return Ok(None);
+            }
+        }
+        Ok(Some(self.line[self.pos]))
+    }
+
+    fn next_char(&mut self) -> Result<Option<char>, PgnParseError> {
+        let c = self.peek_char()?;
+        if c.is_some() {
+            self.pos += 1;
+        }
+        Ok(c)
+    }
+
+    fn read_tag(&mut self) -> Result<Token, PgnParseError> {
+        let mut name = String::new();
+        let mut value = String::new();
+        while let Some(c) = self.peek_char()? {
+            if c.is_whitespace() && name.is_empty() {
+                self.pos += 1;
+            } else if c.is_alphanumeric() || c == '_' {
+                name.push(c);
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        while let Some(c) = self.peek_char()? {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.pos += 1;
+        }
+        if name.is_empty() || self.next_char()? != Some('"') {
+            return Err(PgnParseError::InvalidTag(name));
+        }
+        loop {
+            match self.next_char()? {
+                Some('\\') => match self.next_char()? {
+                    Some(c) => value.push(c),
+                    None => return Err(PgnParseError::InvalidTag(name)),
+                },
+                Some('"') => break,
+                Some('\n') | Some('\r') | None => return Err(PgnParseError::InvalidTag(name)),
+                Some(c) => value.push(c),
+            }
+        }
+        while let Some(c) = self.next_char()? {
+            match c {
+                ']' => return Ok(Token::Tag(name, value)),
+                c if c.is_whitespace() => {}
+                _ => break,
+            }
+        }
+        Err(PgnParseError::InvalidTag(name))
+    }
+
+    fn read_comment(&mut self) -> Result<Token, PgnParseError> {
+        let mut text = String::new();
+        loop {
+            match self.next_char()? {
+                Some('}') => break,
+                Some(c) => text.push(c),
+                None => return Err(PgnParseError::UnexpectedEof),
+            }
+        }
+        Ok(Token::Comment(text.trim().to_string()))
+    }
+
+    fn read_line_comment(&mut self) -> Result<Token, PgnParseError> {
+        let text: String = self.line[self.pos..].iter().collect();
+        self.pos = self.line.len();
+        Ok(Token::Comment(text.trim().to_string()))
+    }
+
+    fn read_nag(&mut self) -> Result<Token, PgnParseError> {
+        let mut digits = String::new();
+        while let Some(c) = self.peek_char()? {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            digits.push(c);
+            self.pos += 1;
+        }
+        match digits.parse::<u8>() {
+            Ok(nag) => Ok(Token::Nag(nag)),
+            Err(_) => Err(PgnParseError::UnexpectedToken(format!("${}", digits))),
+        }
+    }
+
+    fn read_symbol(&mut self) -> Result<Token, PgnParseError> {
+        let mut symbol = String::new();
+        while let Some(c) = self.peek_char()? {
+            if c.is_alphanumeric() || "_+#=:-/".contains(c) {
+                symbol.push(c);
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        match symbol.as_str() {
+            "1-0" | "0-1" | "1/2-1/2" => return Ok(Token::Result(symbol)),
+            _ => {}
+        }
+        if symbol.chars().all(|c| c.is_ascii_digit()) {
+            while self.peek_char()? == Some('.') {
+                self.pos += 1;
+            }
+            return Ok(Token::MoveNumber);
+        }
+        Ok(Token::San(symbol))
+    }
+
+    fn read_suffix_annotation(&mut self) -> Result<Token, PgnParseError> {
+        let mut glyph = String::new();
+        while let Some(c) = self.peek_char()? {
+            if c != '!' && c != '?' {
+                break;
+            }
+            glyph.push(c);
+            self.pos += 1;
+        }
+        match glyph.as_str() {
+            "!" => Ok(Token::Nag(1)),
+            "?" => Ok(Token::Nag(2)),
+            "!!" => Ok(Token::Nag(3)),
+            "??" => Ok(Token::Nag(4)),
+            "!?" => Ok(Token::Nag(5)),
+            "?!" => Ok(Token::Nag(6)),
+            _ => Err(PgnParseError::UnexpectedToken(glyph)),
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, PgnParseError> {
+        if let Some(token) = self.peeked.take() {
+            return Ok(Some(token));
+        }
+        loop {
+            let c = match self.peek_char()? {
+                Some(c) => c,
+                None => return Ok(None),
+            };
+            let token = match c {
+                c if c.is_whitespace() || c == '.' => {
+                    self.pos += 1;
+                    continue;
+                }
+                '[' => {
+                    self.pos += 1;
+                    self.read_tag()?
+                }
+                '{' => {
+                    self.pos += 1;
+                    self.read_comment()?
+                }
+                ';' => {
+                    self.pos += 1;
+                    self.read_line_comment()?
+                }
+                '$' => {
+                    self.pos += 1;
+                    self.read_nag()?
+                }
+                '(' => {
+                    self.pos += 1;
+                    Token::VariationStart
+                }
+                ')' => {
+                    self.pos += 1;
+                    Token::VariationEnd
+                }
+                '*' => {
+                    self.pos += 1;
+                    Token::Result("*".to_string())
+                }
+                '!' | '?' => self.read_suffix_annotation()?,
+                c if c.is_alphanumeric() => self.read_symbol()?,
+                c => {
+                    self.pos += 1;
+                    return Err(PgnParseError::UnexpectedToken(c.to_string()));
+                }
+            };
+            return Ok(Some(token));
+        }
+    }
+
+    fn read_game(&mut self) -> Option<Result<PgnGame, PgnParseError>> {
+        let mut headers: Vec<(String, String)> = vec![];
+        let mut moves: Vec<Move> = vec![];
+        let mut comments: Vec<(usize, String)> = vec![];
+        let mut nags: Vec<(usize, u8)> = vec![];
+        let mut result: Option<String> = None;
+        let mut board: Option<Board> = None;
+        let mut starting_position: Option<Board> = None;
+        let mut variation_depth = 0;
+        let mut seen_movetext = false;
+        let mut error: Option<PgnParseError> = None;
+
+        loop {
+            let token = match self.next_token() {
+                Ok(Some(token)) => token,
+                Ok(None) => break,
+                Err(PgnParseError::Io(e)) => return Some(Err(PgnParseError::Io(e))),
+                Err(e) => {
+                    // keep consuming the rest of this game, then report the first error
+                    error.get_or_insert(e);
+                    continue;
+                }
+            };
+            match token {
+                Token::Tag(name, value) => {
+                    if seen_movetext {
+                        // missing game termination marker; this tag belongs to the next game
+                        self.peeked = Some(Token::Tag(name, value));
+                        break;
+                    }
+                    headers.push((name, value));
+                    continue;
+                }
+                Token::Result(r) if variation_depth == 0 => {
+                    result = Some(r);
+                    break;
+                }
+                _ => {}
+            }
+
+            seen_movetext = true;
+            if board.is_none() {
+                let initial_board = match headers.iter().find(|(n, _)| n == "FEN") {
+                    Some((_, fen)) => match Board::parse_fen(fen) {
+                        Ok(b) => b,
+                        Err(_) => {
+                            error.get_or_insert(PgnParseError::InvalidFen(fen.clone()));
+                            Board::starting_position()
+                        }
+                    },
+                    None => Board::starting_position(),
+                };
+                starting_position = Some(initial_board.clone());
+                board = Some(initial_board);
+            }
+
+            match token {
+                Token::VariationStart => variation_depth += 1,
+                Token::VariationEnd => {
+                    if variation_depth == 0 {
+                        error.get_or_insert(PgnParseError::UnexpectedToken(")".to_string()));
+                    } else {
+                        variation_depth -= 1;
+                    }
+                }
+                _ if variation_depth > 0 => {
+                    // moves (and their annotations) inside variations are skipped
+                }
+                Token::San(san) => {
+                    if error.is_some() {
+                        continue;
+                    }
+                    match board.as_mut().unwrap().push_san(&san) {
+                        Ok(move_) => moves.push(move_),
+                        Err(_) => error = Some(PgnParseError::IllegalMove(san)),
+                    }
+                }
+                Token::Comment(text) => comments.push((moves.len(), text)),
+                Token::Nag(nag) => nags.push((moves.len(), nag)),
+                _ => {}
+            }
+        }
+
+        if !seen_movetext && headers.is_empty() {
+            return error.map(Err);
+        }
+        if let Some(e) = error {
+            return Some(Err(e));
+        }
+        let board = match board {
+            Some(b) => b,
+            None => match headers.iter().find(|(n, _)| n == "FEN") {
+                Some((_, fen)) => match Board::parse_fen(fen) {
+                    Ok(b) => b,
+                    Err(_) => return Some(Err(PgnParseError::InvalidFen(fen.clone()))),
+                },
+                None => Board::starting_position(),
+            },
+        };
+        Some(Ok(PgnGame {
+            headers,
+            starting_position: starting_position.unwrap_or_else(|| board.clone()),
+            moves,
+            comments,
+            nags,
+            result: result.unwrap_or_else(|| "*".to_string()),
+            board,
+        }))
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, PgnParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_game()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const OPERA_GAME: &str = r#"[Event "Paris"]
+[Site "Paris FRA"]
+[Date "1858.??.??"]
+[Round "?"]
+[White "Paul Morphy"]
+[Black "Duke Karl / Count Isouard"]
+[Result "1-0"]
+
+1.e4 e5 2.Nf3 d6 3.d4 Bg4 {This is a weak move already.--Fischer} 4.dxe5 Bxf3
+5.Qxf3 dxe5 6.Bc4 Nf6 7.Qb3 Qe7 8.Nc3 c6 9.Bg5 {Black is in what's like a
+zugzwang position here.} b5 10.Nxb5! cxb5 11.Bxb5+ Nbd7 12.O-O-O Rd8
+13.Rxd7 Rxd7 14.Rd1 Qe6 15.Bxd7+ Nxd7 16.Qb8+!! Nxb8 17.Rd8# 1-0
+"#;
+
+    #[test]
+    fn test_read_single_game() {
+        let mut reader = PgnReader::new(Cursor::new(OPERA_GAME));
+        let game = reader.next().unwrap().unwrap();
+        assert!(reader.next().is_none());
+        assert_eq!(game.header("White"), Some("Paul Morphy"));
+        assert_eq!(game.header("Black"), Some("Duke Karl / Count Isouard"));
+        assert_eq!(game.moves.len(), 33);
+        assert_eq!(game.result, "1-0");
+        assert_eq!(game.comments.len(), 2);
+        assert_eq!(game.comments[0].0, 6);
+        assert_eq!(game.nags, vec![(19, 1), (31, 3)]);
+        assert!(game.board.is_in_check());
+        assert!(game.board.legal_moves().is_empty());
+        assert_eq!(game.board.to_fen_string(), "1n1Rkb1r/p4ppp/4q3/4p1B1/4P3/8/PPP2PPP/2K5 b k - 1 17");
+    }
+
+    #[test]
+    fn test_read_multiple_games() {
+        let pgn = r#"[Event "Game 1"]
+[Result "0-1"]
+
+1. f3 e5 2. g4 Qh4# 0-1
+
+[Event "Game 2"]
+[Result "*"]
+
+1. e4 (1. d4 d5 (1... Nf6) 2. c4) 1... c5 $1 2. Nf3 ; Sicilian
+*
+[Event "Game 3"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+[SetUp "1"]
+
+1. e4 Kd7 1/2-1/2
+"#;
+        let games = PgnReader::new(Cursor::new(pgn))
+            .collect::<Result<Vec<PgnGame>, PgnParseError>>()
+            .unwrap();
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[0].header("Event"), Some("Game 1"));
+        assert_eq!(games[0].moves.len(), 4);
+        assert_eq!(games[0].result, "0-1");
+        assert_eq!(games[1].moves.len(), 3);
+        assert_eq!(games[1].nags, vec![(2, 1)]);
+        assert_eq!(games[1].comments, vec![(3, "Sicilian".to_string())]);
+        assert_eq!(games[1].result, "*");
+        assert_eq!(games[2].starting_position.to_fen_string(), "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        assert_eq!(games[2].board.to_fen_string(), "8/3k4/8/8/4P3/8/8/4K3 w - - 1 2");
+    }
+
+    #[test]
+    fn test_illegal_move_skips_to_next_game() {
+        let pgn = "1. e4 e5 2. Ke3 Nc6 1-0\n\n1. d4 d5 0-1\n";
+        let mut reader = PgnReader::new(Cursor::new(pgn));
+        match reader.next() {
+            Some(Err(PgnParseError::IllegalMove(san))) => assert_eq!(san, "Ke3"),
+            _ => panic!("expected an illegal move error"),
+        }
+        let game = reader.next().unwrap().unwrap();
+        assert_eq!(game.moves.len(), 2);
+        assert_eq!(game.result, "0-1");
+        assert!(reader.next().is_none());
+    }
+}
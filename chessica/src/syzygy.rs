@@ -0,0 +1,1343 @@
+use std::collections::HashMap;
+use std::ops;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use lazy_static::lazy_static;
+
+use crate::board::Board;
+use crate::{Move, Piece, Side};
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+const MAX_PIECES: usize = 7;
+
+// flags in the first byte after the magic
+const SPLIT: u8 = 1;
+const HAS_PAWNS: u8 = 2;
+
+// flags of each compressed sub-table
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Wdl {
+    Loss,
+    // loss that can be saved by the fifty move rule
+    BlessedLoss,
+    Draw,
+    // win that is spoiled by the fifty move rule
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Option<Wdl> {
+        match value {
+            -2 => Some(Wdl::Loss),
+            -1 => Some(Wdl::BlessedLoss),
+            0 => Some(Wdl::Draw),
+            1 => Some(Wdl::CursedWin),
+            2 => Some(Wdl::Win),
+            _ => None,
+        }
+    }
+
+    pub fn value(self) -> i32 {
+        match self {
+            Wdl::Loss => -2,
+            Wdl::BlessedLoss => -1,
+            Wdl::Draw => 0,
+            Wdl::CursedWin => 1,
+            Wdl::Win => 2,
+        }
+    }
+}
+
+impl ops::Neg for Wdl {
+    type Output = Wdl;
+
+    fn neg(self) -> Self::Output {
+        Wdl::from_value(-self.value()).unwrap()
+    }
+}
+
+fn off_a1h8(square: usize) -> i32 {
+    (square / 8) as i32 - (square % 8) as i32
+}
+
+fn king_distance(a: usize, b: usize) -> usize {
+    let rank_distance = (a / 8).abs_diff(b / 8);
+    let file_distance = (a % 8).abs_diff(b % 8);
+    rank_distance.max(file_distance)
+}
+
+// the tables used to turn a position into an index, see Encoding::new
+struct Encoding {
+    map_b1h1h7: [u64; 64],
+    map_a1d1d4: [usize; 64],
+    map_kk: [[u64; 64]; 10],
+    binomial: [[u64; 64]; 7],
+    map_pawns: [usize; 64],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+lazy_static! {
+    static ref ENCODING: Encoding = Encoding::new();
+}
+
+impl Encoding {
+    fn new() -> Self {
+        let mut e = Encoding {
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; 7],
+            map_pawns: [0; 64],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        // squares below the a1-h8 diagonal
+        let mut code = 0;
+        for square in 0..64 {
+            if off_a1h8(square) < 0 {
+                e.map_b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+
+        // squares of the a1-d1-d4 triangle, the ones on the diagonal last
+        let mut code = 0;
+        let mut diagonal = vec![];
+        for square in 0..28 {
+            if square % 8 > 3 {
+                continue;
+            }
+            if off_a1h8(square) < 0 {
+                e.map_a1d1d4[square] = code;
+                code += 1;
+            } else if off_a1h8(square) == 0 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            e.map_a1d1d4[square] = code;
+            code += 1;
+        }
+
+        // the 462 legal placements of two kings with the first one in the a1-d1-d4 triangle;
+        // if the first king is on the diagonal, the second one is not above it
+        let mut code = 0;
+        let mut both_on_diagonal = vec![];
+        for idx in 0..10 {
+            for first in 0..28 {
+                // b1 is the square mapped to 0, every other unmapped square also reads 0
+                if e.map_a1d1d4[first] != idx || (idx == 0 && first != 1) || first % 8 > 3 {
+                    continue;
+                }
+                for second in 0..64 {
+                    if king_distance(first, second) <= 1 {
+                        continue;
+                    }
+                    if off_a1h8(first) == 0 && off_a1h8(second) > 0 {
+                        continue;
+                    }
+                    if off_a1h8(first) == 0 && off_a1h8(second) == 0 {
+                        both_on_diagonal.push((idx, second));
+                    } else {
+                        e.map_kk[idx][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, second) in both_on_diagonal {
+            e.map_kk[idx][second] = code;
+            code += 1;
+        }
+
+        e.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..7.min(n + 1) {
+                e.binomial[k][n] = if k > 0 { e.binomial[k - 1][n - 1] } else { 0 }
+                    + if k < n { e.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        // map_pawns numbers a2-h7 so that the leading pawn, the one closest to the edge and
+        // then to rank 2, has the highest value
+        let mut available_squares = 47i32;
+        for lead_pawns_count in 1..6 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let square = rank * 8 + file;
+                    if lead_pawns_count == 1 {
+                        e.map_pawns[square] = available_squares as usize;
+                        e.map_pawns[square ^ 7] = (available_squares - 1) as usize;
+                        available_squares -= 2;
+                    }
+                    e.lead_pawn_idx[lead_pawns_count][square] = idx;
+                    idx += e.binomial[lead_pawns_count - 1][e.map_pawns[square]];
+                }
+                e.lead_pawns_size[lead_pawns_count][file] = idx;
+            }
+        }
+        e
+    }
+}
+
+fn read_u8(data: &[u8], pos: usize) -> Option<u8> {
+    data.get(pos).copied()
+}
+
+fn read_u16_le(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn read_u32_le(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+// the compressed stream may be read a few bytes past the end of the last block
+fn read_u32_be_padded(data: &[u8], pos: usize) -> u32 {
+    (0..4).fold(0, |acc, i| acc << 8 | *data.get(pos + i).unwrap_or(&0) as u32)
+}
+
+// a binary tree node packing two 12 bit symbols into 3 bytes
+fn btree_left(data: &[u8], btree: usize, sym: usize) -> Option<usize> {
+    let lr = data.get(btree + 3 * sym..btree + 3 * sym + 3)?;
+    Some(((lr[1] as usize & 0xf) << 8) | lr[0] as usize)
+}
+
+fn btree_right(data: &[u8], btree: usize, sym: usize) -> Option<usize> {
+    let lr = data.get(btree + 3 * sym..btree + 3 * sym + 3)?;
+    Some(((lr[2] as usize) << 4) | (lr[1] as usize >> 4))
+}
+
+// One compressed sub-table: values for a side to move (and leading pawn file) are stored as
+// a sequence of canonical Huffman codes of "recursive pairing" symbols, split into blocks
+#[derive(Debug, Clone, Default)]
+struct PairsData {
+    flags: u8,
+    pieces: [u8; MAX_PIECES],
+    group_len: [usize; MAX_PIECES + 1],
+    group_idx: [u64; MAX_PIECES + 1],
+    // the stored value if FLAG_SINGLE_VALUE is set
+    min_sym_len: u8,
+    sizeof_block: u64,
+    span: u64,
+    sparse_index_size: usize,
+    block_length_size: usize,
+    blocks_num: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u8>,
+    // offsets into the table data
+    lowest_sym: usize,
+    btree: usize,
+    sparse_index: usize,
+    block_length: usize,
+    data: usize,
+    map_idx: [u16; 4],
+}
+
+impl PairsData {
+    fn set_groups(&mut self, table: &TableInfo, order: [u8; 2], file: usize) {
+        let encoding = &*ENCODING;
+        let mut first_len: i32 = if table.has_pawns { 0 } else if table.has_unique_pieces { 3 } else { 2 };
+        let mut n = 0;
+        self.group_len[0] = 1;
+        for i in 1..table.piece_count {
+            first_len -= 1;
+            if first_len > 0 || self.pieces[i] == self.pieces[i - 1] {
+                self.group_len[n] += 1;
+            } else {
+                n += 1;
+                self.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        self.group_len[n] = 0;
+
+        // the groups are not necessarily encoded in the order they appear in: order[0] is the
+        // position of the leading group and order[1] the one of the remaining pawns
+        let pp = table.has_pawns && table.pawn_count[1] > 0;
+        let mut next = if pp { 2 } else { 1 };
+        let mut free_squares = 64 - self.group_len[0] - if pp { self.group_len[1] } else { 0 };
+        let mut idx: u64 = 1;
+        let mut k = 0;
+        while next < n || k == order[0] || k == order[1] {
+            if k == order[0] {
+                self.group_idx[0] = idx;
+                idx *= if table.has_pawns {
+                    encoding.lead_pawns_size[self.group_len[0]][file]
+                } else if table.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] {
+                self.group_idx[1] = idx;
+                idx *= encoding.binomial[self.group_len[1]][48 - self.group_len[0]];
+            } else {
+                self.group_idx[next] = idx;
+                idx *= encoding.binomial[self.group_len[next]][free_squares];
+                free_squares -= self.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        self.group_idx[n] = idx;
+    }
+
+    fn set_sizes(&mut self, data: &[u8], mut pos: usize) -> Option<usize> {
+        self.flags = read_u8(data, pos)?;
+        pos += 1;
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            self.min_sym_len = read_u8(data, pos)?;
+            return Some(pos + 1);
+        }
+
+        let groups = self.group_len.iter().position(|&len| len == 0)?;
+        let tb_size = self.group_idx[groups];
+        self.sizeof_block = 1u64.checked_shl(read_u8(data, pos)? as u32)?;
+        self.span = 1u64.checked_shl(read_u8(data, pos + 1)? as u32)?;
+        self.sparse_index_size = tb_size.div_ceil(self.span) as usize;
+        let padding = read_u8(data, pos + 2)? as usize;
+        self.blocks_num = read_u32_le(data, pos + 3)? as usize;
+        // padded so that the sparse index never points past the block lengths
+        self.block_length_size = self.blocks_num + padding;
+        let max_sym_len = read_u8(data, pos + 7)?;
+        self.min_sym_len = read_u8(data, pos + 8)?;
+        pos += 9;
+        if max_sym_len < self.min_sym_len || max_sym_len as u32 > 64 {
+            return None;
+        }
+
+        // canonical Huffman codes: longer codes have lower values, lowest_sym[i] is the first
+        // symbol with a code of length min_sym_len + i and base64[i] its code, left aligned
+        self.lowest_sym = pos;
+        let lengths = (max_sym_len - self.min_sym_len + 1) as usize;
+        self.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = read_u16_le(data, pos + 2 * i)? as u64;
+            let next_lowest = read_u16_le(data, pos + 2 * i + 2)? as u64;
+            self.base64[i] = self.base64[i + 1].wrapping_add(lowest).wrapping_sub(next_lowest) / 2;
+        }
+        for (i, base) in self.base64.iter_mut().enumerate() {
+            *base = base.checked_shl(64 - i as u32 - self.min_sym_len as u32).unwrap_or(0);
+        }
+        pos += 2 * lengths;
+
+        let symbols = read_u16_le(data, pos)? as usize;
+        pos += 2;
+        self.btree = pos;
+        self.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                self.symlen[sym] = self.set_symlen(data, sym, &mut visited)?;
+            }
+        }
+        Some(pos + 3 * symbols + (symbols & 1))
+    }
+
+    // the number of values a symbol expands to, minus one
+    fn set_symlen(&mut self, data: &[u8], sym: usize, visited: &mut [bool]) -> Option<u8> {
+        visited[sym] = true;
+        let right = btree_right(data, self.btree, sym)?;
+        if right == 0xfff {
+            return Some(0);
+        }
+        let left = btree_left(data, self.btree, sym)?;
+        if left >= self.symlen.len() || right >= self.symlen.len() {
+            return None;
+        }
+        if !visited[left] {
+            self.symlen[left] = self.set_symlen(data, left, visited)?;
+        }
+        if !visited[right] {
+            self.symlen[right] = self.set_symlen(data, right, visited)?;
+        }
+        Some(self.symlen[left].wrapping_add(self.symlen[right]).wrapping_add(1))
+    }
+
+    fn block_length(&self, data: &[u8], block: i64) -> Option<i64> {
+        if block < 0 || block as usize >= self.block_length_size {
+            return None;
+        }
+        Some(read_u16_le(data, self.block_length + 2 * block as usize)? as i64)
+    }
+
+    fn decompress(&self, data: &[u8], idx: u64) -> Option<i32> {
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(self.min_sym_len as i32);
+        }
+
+        // the sparse index stores, every `span` values, the block and offset in the block of
+        // the value in the middle of the span; walk from there to the block holding idx
+        let k = (idx / self.span) as usize;
+        if k >= self.sparse_index_size {
+            return None;
+        }
+        let entry = self.sparse_index + 6 * k;
+        let mut block = read_u32_le(data, entry)? as i64;
+        let mut offset = read_u16_le(data, entry + 4)? as i64;
+        offset += (idx % self.span) as i64 - (self.span / 2) as i64;
+        while offset < 0 {
+            block -= 1;
+            offset += self.block_length(data, block)? + 1;
+        }
+        while offset > self.block_length(data, block)? {
+            offset -= self.block_length(data, block)? + 1;
+            block += 1;
+        }
+
+        let mut ptr = self.data + block as usize * self.sizeof_block as usize;
+        let mut buf64 = (read_u32_be_padded(data, ptr) as u64) << 32 | read_u32_be_padded(data, ptr + 4) as u64;
+        ptr += 8;
+        let mut buf64_size = 64;
+        let min_sym_len = self.min_sym_len as usize;
+        let mut sym;
+        loop {
+            let mut len = 0;
+            while buf64 < self.base64[len] {
+                len += 1;
+                if len == self.base64.len() {
+                    return None;
+                }
+            }
+            sym = ((buf64 - self.base64[len]) >> (64 - len - min_sym_len)) as usize;
+            sym += read_u16_le(data, self.lowest_sym + 2 * len)? as usize;
+            if sym >= self.symlen.len() {
+                return None;
+            }
+            if offset < self.symlen[sym] as i64 + 1 {
+                break;
+            }
+            offset -= self.symlen[sym] as i64 + 1;
+            let len = len + min_sym_len;
+            buf64 <<= len;
+            buf64_size -= len;
+            if buf64_size <= 32 {
+                buf64_size += 32;
+                buf64 |= (read_u32_be_padded(data, ptr) as u64) << (64 - buf64_size);
+                ptr += 4;
+            }
+        }
+
+        // expand the pair symbol down to the leaf holding our value
+        while self.symlen[sym] != 0 {
+            let left = btree_left(data, self.btree, sym)?;
+            if offset < self.symlen[left] as i64 + 1 {
+                sym = left;
+            } else {
+                offset -= self.symlen[left] as i64 + 1;
+                sym = btree_right(data, self.btree, sym)?;
+            }
+        }
+        Some(btree_left(data, self.btree, sym)? as i32)
+    }
+}
+
+// what the table name tells about its material; "white" is the side named first
+#[derive(Debug, Clone)]
+struct TableInfo {
+    symmetric: bool,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    // pawns of the leading colour first
+    pawn_count: [usize; 2],
+    piece_count: usize,
+}
+
+impl TableInfo {
+    fn new(name: &str) -> Option<Self> {
+        let (white, black) = name.split_once('v')?;
+        let white_pawns = white.matches('P').count();
+        let black_pawns = black.matches('P').count();
+        // the side with fewer pawns leads, since that compresses better
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        Some(TableInfo {
+            symmetric: white == black,
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces: [white, black]
+                .iter()
+                .any(|side| "PNBRQ".chars().any(|c| side.matches(c).count() == 1)),
+            pawn_count: if white_leads { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] },
+            piece_count: white.len() + black.len(),
+        })
+    }
+}
+
+struct Table {
+    data: Vec<u8>,
+    info: TableInfo,
+    // indexed by [side to move][leading pawn file]; only WDL tables of asymmetric material
+    // store both sides, only tables with pawns store one table per file
+    items: Vec<Vec<PairsData>>,
+    // DTZ value maps
+    map: usize,
+}
+
+impl Table {
+    fn parse(name: &str, data: Vec<u8>, is_wdl: bool) -> Option<Self> {
+        let magic = if is_wdl { WDL_MAGIC } else { DTZ_MAGIC };
+        if data.get(0..4)? != magic {
+            return None;
+        }
+        let info = TableInfo::new(name)?;
+        let flags = read_u8(&data, 4)?;
+        if (flags & HAS_PAWNS != 0) != info.has_pawns || (flags & SPLIT != 0) == info.symmetric {
+            return None;
+        }
+        let mut pos = 5;
+
+        let sides = if is_wdl && !info.symmetric { 2 } else { 1 };
+        let files = if info.has_pawns { 4 } else { 1 };
+        let pp = info.has_pawns && info.pawn_count[1] > 0;
+        let mut items = vec![vec![PairsData::default(); files]; sides];
+        for file in 0..files {
+            let order_byte = read_u8(&data, pos)?;
+            let pp_order_byte = if pp { read_u8(&data, pos + 1)? } else { 0xff };
+            let orders = [[order_byte & 0xf, pp_order_byte & 0xf], [order_byte >> 4, pp_order_byte >> 4]];
+            pos += if pp { 2 } else { 1 };
+            for k in 0..info.piece_count {
+                let pieces = read_u8(&data, pos)?;
+                pos += 1;
+                for (side, side_items) in items.iter_mut().enumerate() {
+                    side_items[file].pieces[k] = if side == 0 { pieces & 0xf } else { pieces >> 4 };
+                }
+            }
+            for (side, side_items) in items.iter_mut().enumerate() {
+                side_items[file].set_groups(&info, orders[side], file);
+            }
+        }
+        pos += pos & 1;
+
+        for file in 0..files {
+            for side_items in items.iter_mut() {
+                pos = side_items[file].set_sizes(&data, pos)?;
+            }
+        }
+
+        let map = pos;
+        if !is_wdl {
+            for d in items[0].iter_mut() {
+                if d.flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                // one map each for wins, losses, cursed wins and blessed losses
+                if d.flags & FLAG_WIDE != 0 {
+                    pos += pos & 1;
+                    for i in 0..4 {
+                        d.map_idx[i] = ((pos - map) / 2 + 1) as u16;
+                        pos += 2 * read_u16_le(&data, pos)? as usize + 2;
+                    }
+                } else {
+                    for i in 0..4 {
+                        d.map_idx[i] = (pos - map + 1) as u16;
+                        pos += read_u8(&data, pos)? as usize + 1;
+                    }
+                }
+            }
+            pos += pos & 1;
+        }
+
+        for file in 0..files {
+            for side_items in items.iter_mut() {
+                side_items[file].sparse_index = pos;
+                pos += 6 * side_items[file].sparse_index_size;
+            }
+        }
+        for file in 0..files {
+            for side_items in items.iter_mut() {
+                side_items[file].block_length = pos;
+                pos += 2 * side_items[file].block_length_size;
+            }
+        }
+        for file in 0..files {
+            for side_items in items.iter_mut() {
+                pos = (pos + 0x3f) & !0x3f;
+                side_items[file].data = pos;
+                pos += side_items[file].blocks_num * side_items[file].sizeof_block as usize;
+            }
+        }
+        if pos > data.len() {
+            return None;
+        }
+
+        Some(Table { data, info, items, map })
+    }
+
+    // DTZ values are stored as moves unless the flags say plies, and may go through a map
+    fn map_dtz_score(&self, file: usize, value: i32, wdl: Wdl) -> Option<i32> {
+        const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+        let d = &self.items[0][file];
+        let mut value = value;
+        if d.flags & FLAG_MAPPED != 0 {
+            let idx = d.map_idx[WDL_MAP[(wdl.value() + 2) as usize]] as usize + value as usize;
+            value = if d.flags & FLAG_WIDE != 0 {
+                read_u16_le(&self.data, self.map + 2 * idx)? as i32
+            } else {
+                read_u8(&self.data, self.map + idx)? as i32
+            };
+        }
+        if (wdl == Wdl::Win && d.flags & FLAG_WIN_PLIES == 0)
+            || (wdl == Wdl::Loss && d.flags & FLAG_LOSS_PLIES == 0)
+            || wdl == Wdl::CursedWin
+            || wdl == Wdl::BlessedLoss
+        {
+            value *= 2;
+        }
+        Some(value + 1)
+    }
+}
+
+enum TableValue {
+    Value(i32),
+    // the DTZ table only stores the other side to move
+    ChangeSideToMove,
+}
+
+fn tb_piece(side: Side, piece: Piece) -> u8 {
+    piece as u8 | if side == Side::Black { 8 } else { 0 }
+}
+
+fn material_name(board: &Board, side: Side) -> String {
+    let mut name = String::new();
+    for (piece, c) in [
+        (Piece::King, 'K'),
+        (Piece::Queen, 'Q'),
+        (Piece::Rook, 'R'),
+        (Piece::Bishop, 'B'),
+        (Piece::Knight, 'N'),
+        (Piece::Pawn, 'P'),
+    ] {
+        for square in board.get_pieces(side) {
+            if board.get_piece(side, square) == Some(piece) {
+                name.push(c);
+            }
+        }
+    }
+    name
+}
+
+fn piece_count(board: &Board) -> usize {
+    (board.get_pieces(Side::White) | board.get_pieces(Side::Black)).count() as usize
+}
+
+fn is_checkmate(board: &Board) -> bool {
+    board.is_in_check() && board.legal_moves().is_empty()
+}
+
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+    }
+}
+
+// Computes the index of a position in a table: the squares are mirrored so that the leading
+// piece or pawn is in a canonical part of the board, then each group of pieces is encoded
+fn encode(board: &Board, info: &TableInfo, items: &[Vec<PairsData>], flip: bool, is_wdl: bool) -> Option<(usize, usize, u64)> {
+    let encoding = &*ENCODING;
+    let flip_color = if flip { 8 } else { 0 };
+    let flip_squares = if flip { 56 } else { 0 };
+    let stm = flip as usize ^ (board.side_to_move() == Side::Black) as usize;
+
+    let mut squares = [0usize; MAX_PIECES];
+    let mut pieces = [0u8; MAX_PIECES];
+    let mut size = 0;
+    let mut lead_pawns_count = 0;
+    let mut lead_pawns_side = None;
+    let mut file = 0;
+
+    if info.has_pawns {
+        let lead_piece = items[0][0].pieces[0] ^ flip_color;
+        let side = if lead_piece & 8 != 0 { Side::Black } else { Side::White };
+        for square in board.get_pieces(side) {
+            if board.get_piece(side, square) == Some(Piece::Pawn) {
+                squares[size] = square.ordinal as usize ^ flip_squares;
+                pieces[size] = lead_piece;
+                size += 1;
+            }
+        }
+        lead_pawns_count = size;
+        lead_pawns_side = Some(side);
+        let lead = (0..size).max_by_key(|&i| encoding.map_pawns[squares[i]])?;
+        squares.swap(0, lead);
+        file = (squares[0] % 8).min(7 - squares[0] % 8);
+    }
+
+    if !is_wdl {
+        let flags = items[0][file].flags;
+        // symmetric pawnless tables store both sides to move in one
+        let both_sides = info.symmetric && !info.has_pawns;
+        if !both_sides && (flags & FLAG_STM) as usize != stm {
+            return None;
+        }
+    }
+
+    for side in [Side::White, Side::Black] {
+        for square in board.get_pieces(side) {
+            let piece = board.get_piece(side, square)?;
+            if piece == Piece::Pawn && lead_pawns_side == Some(side) {
+                continue;
+            }
+            if size == MAX_PIECES {
+                return None;
+            }
+            squares[size] = square.ordinal as usize ^ flip_squares;
+            pieces[size] = tb_piece(side, piece) ^ flip_color;
+            size += 1;
+        }
+    }
+
+    let d = &items[stm % items.len()][file];
+
+    // order the pieces like the table does
+    for i in lead_pawns_count..size.saturating_sub(1) {
+        for j in i + 1..size {
+            if d.pieces[i] == pieces[j] {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+                break;
+            }
+        }
+    }
+
+    if squares[0] % 8 > 3 {
+        for square in squares[..size].iter_mut() {
+            *square ^= 7;
+        }
+    }
+
+    let mut idx;
+    if info.has_pawns {
+        idx = encoding.lead_pawn_idx[lead_pawns_count][squares[0]];
+        squares[1..lead_pawns_count].sort_by_key(|&s| encoding.map_pawns[s]);
+        for (i, &square) in squares[..lead_pawns_count].iter().enumerate().skip(1) {
+            idx += encoding.binomial[i][encoding.map_pawns[square]];
+        }
+    } else {
+        if squares[0] / 8 > 3 {
+            for square in squares[..size].iter_mut() {
+                *square ^= 56;
+            }
+        }
+        // the first piece of the leading group that is off the a1-h8 diagonal must be below it
+        for i in 0..d.group_len[0] {
+            let off = off_a1h8(squares[i]);
+            if off == 0 {
+                continue;
+            }
+            if off > 0 {
+                for square in squares[i..size].iter_mut() {
+                    *square = ((*square >> 3) | (*square << 3)) & 63;
+                }
+            }
+            break;
+        }
+
+        if info.has_unique_pieces {
+            let rank = |s: usize| (s / 8) as u64;
+            let adjust1 = (squares[1] > squares[0]) as u64;
+            let adjust2 = (squares[2] > squares[0]) as u64 + (squares[2] > squares[1]) as u64;
+            idx = if off_a1h8(squares[0]) != 0 {
+                (encoding.map_a1d1d4[squares[0]] as u64 * 63 + (squares[1] as u64 - adjust1)) * 62 + squares[2] as u64 - adjust2
+            } else if off_a1h8(squares[1]) != 0 {
+                (6 * 63 + rank(squares[0]) * 28 + encoding.map_b1h1h7[squares[1]]) * 62 + squares[2] as u64 - adjust2
+            } else if off_a1h8(squares[2]) != 0 {
+                6 * 63 * 62
+                    + 4 * 28 * 62
+                    + rank(squares[0]) * 7 * 28
+                    + (rank(squares[1]) - adjust1) * 28
+                    + encoding.map_b1h1h7[squares[2]]
+            } else {
+                6 * 63 * 62
+                    + 4 * 28 * 62
+                    + 4 * 7 * 28
+                    + rank(squares[0]) * 7 * 6
+                    + (rank(squares[1]) - adjust1) * 6
+                    + (rank(squares[2]) - adjust2)
+            };
+        } else {
+            idx = encoding.map_kk[encoding.map_a1d1d4[squares[0]]][squares[1]];
+        }
+    }
+
+    idx *= d.group_idx[0];
+    let mut group_start = d.group_len[0];
+    let mut remaining_pawns = info.has_pawns && info.pawn_count[1] > 0;
+    let mut next = 1;
+    while d.group_len[next] != 0 {
+        let group_end = group_start + d.group_len[next];
+        squares[group_start..group_end].sort();
+        let mut n = 0;
+        for i in 0..d.group_len[next] {
+            let square = squares[group_start + i];
+            let adjust = squares[..group_start].iter().filter(|&&s| square > s).count();
+            n += encoding.binomial[i + 1][square - adjust - if remaining_pawns { 8 } else { 0 }];
+        }
+        remaining_pawns = false;
+        idx += n * d.group_idx[next];
+        group_start = group_end;
+        next += 1;
+    }
+
+    Some((stm % items.len(), file, idx))
+}
+
+// Syzygy WDL (.rtbw) and DTZ (.rtbz) tables found in one or more directories. Tables are
+// loaded on first use.
+pub struct Tablebase {
+    files: HashMap<String, PathBuf>,
+    max_pieces: usize,
+    wdl_tables: RwLock<HashMap<String, Option<Arc<Table>>>>,
+    dtz_tables: RwLock<HashMap<String, Option<Arc<Table>>>>,
+}
+
+impl Tablebase {
+    // `paths` is a list of directories separated like the PATH variable of the platform
+    pub fn new(paths: &str) -> Self {
+        let mut files = HashMap::new();
+        let mut max_pieces = 0;
+        for dir in std::env::split_paths(paths) {
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                let (name, extension) = match file_name.rsplit_once('.') {
+                    Some(parts) => parts,
+                    None => continue,
+                };
+                if (extension != "rtbw" && extension != "rtbz") || !Tablebase::is_table_name(name) {
+                    continue;
+                }
+                if extension == "rtbw" {
+                    max_pieces = max_pieces.max(name.len() - 1);
+                }
+                files.insert(file_name, entry.path());
+            }
+        }
+        Tablebase {
+            files,
+            max_pieces,
+            wdl_tables: RwLock::new(HashMap::new()),
+            dtz_tables: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn is_table_name(name: &str) -> bool {
+        match name.split_once('v') {
+            Some((white, black)) => {
+                let is_side = |s: &str| s.starts_with('K') && s[1..].chars().all(|c| "QRBNP".contains(c));
+                is_side(white) && is_side(black) && white.len() + black.len() <= MAX_PIECES
+            }
+            None => false,
+        }
+    }
+
+    // the largest number of pieces for which there are WDL tables
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    fn load(&self, name: &str, is_wdl: bool) -> Option<Arc<Table>> {
+        let cache = if is_wdl { &self.wdl_tables } else { &self.dtz_tables };
+        if let Some(table) = cache.read().unwrap().get(name) {
+            return table.clone();
+        }
+        let extension = if is_wdl { "rtbw" } else { "rtbz" };
+        let table = self
+            .files
+            .get(&format!("{}.{}", name, extension))
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|data| Table::parse(name, data, is_wdl))
+            .map(Arc::new);
+        cache.write().unwrap().insert(name.to_string(), table.clone());
+        table
+    }
+
+    fn probe_table(&self, board: &Board, is_wdl: bool, wdl: Wdl) -> Option<TableValue> {
+        let white = material_name(board, Side::White);
+        let black = material_name(board, Side::Black);
+        // tables are named with the stronger side first, if that is black the colours are flipped
+        let (table, black_stronger) = match self.load(&format!("{}v{}", white, black), is_wdl) {
+            Some(table) => (table, false),
+            None => (self.load(&format!("{}v{}", black, white), is_wdl)?, true),
+        };
+        // symmetric tables only store white to move
+        let flip = black_stronger || (table.info.symmetric && board.side_to_move() == Side::Black);
+        let (side, file, idx) = match encode(board, &table.info, &table.items, flip, is_wdl) {
+            Some(encoded) => encoded,
+            None if !is_wdl => return Some(TableValue::ChangeSideToMove),
+            None => return None,
+        };
+        let value = table.items[side][file].decompress(&table.data, idx)?;
+        if is_wdl {
+            Some(TableValue::Value(value - 2))
+        } else {
+            Some(TableValue::Value(table.map_dtz_score(file, value, wdl)?))
+        }
+    }
+
+    fn probe_wdl_table(&self, board: &Board) -> Option<Wdl> {
+        if piece_count(board) == 2 {
+            return Some(Wdl::Draw);
+        }
+        match self.probe_table(board, true, Wdl::Draw)? {
+            TableValue::Value(value) => Wdl::from_value(value),
+            TableValue::ChangeSideToMove => None,
+        }
+    }
+
+    // The tables store arbitrary values for positions where a capture (or, for DTZ, a pawn
+    // move) is best, and ignore en passant, so captures are resolved by searching them first.
+    // Also returns whether the best move is such a zeroing move.
+    fn search(&self, board: &mut Board, check_zeroing_moves: bool) -> Option<(Wdl, bool)> {
+        let moves = board.legal_moves();
+        let mut best = Wdl::Loss;
+        let mut move_count = 0;
+        for move_ in moves.iter() {
+            if !move_.is_capture() && (!check_zeroing_moves || move_.piece() != Piece::Pawn) {
+                continue;
+            }
+            move_count += 1;
+            board.push(move_);
+            let value = self.search(board, false).map(|(wdl, _)| -wdl);
+            board.pop();
+            let value = value?;
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Some((value, true));
+                }
+            }
+        }
+
+        let no_more_moves = move_count > 0 && move_count == moves.len();
+        let value = if no_more_moves { best } else { self.probe_wdl_table(board)? };
+        if best >= value {
+            return Some((best, best > Wdl::Draw || no_more_moves));
+        }
+        Some((value, false))
+    }
+
+    fn can_probe(&self, board: &Board) -> bool {
+        piece_count(board) <= self.max_pieces && !board.has_castling_rights()
+    }
+
+    pub fn probe_wdl(&self, board: &mut Board) -> Option<Wdl> {
+        if !self.can_probe(board) {
+            return None;
+        }
+        self.search(board, false).map(|(wdl, _)| wdl)
+    }
+
+    // Distance to the next capture or pawn move (in plies) on the way to the tablebase
+    // result, positive for wins and negative for losses. 0 means draw, ±1 that the zeroing
+    // move is the next move, and values beyond ±100 are wins or losses spoiled by the fifty
+    // move rule.
+    pub fn probe_dtz(&self, board: &mut Board) -> Option<i32> {
+        if !self.can_probe(board) {
+            return None;
+        }
+        self.dtz(board)
+    }
+
+    fn dtz(&self, board: &mut Board) -> Option<i32> {
+        let (wdl, zeroing_best_move) = self.search(board, true)?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if zeroing_best_move {
+            return Some(dtz_before_zeroing(wdl));
+        }
+        if let TableValue::Value(dtz) = self.probe_table(board, false, wdl)? {
+            let fifty_move_offset = if wdl == Wdl::BlessedLoss || wdl == Wdl::CursedWin { 100 } else { 0 };
+            return Some((dtz + fifty_move_offset) * wdl.value().signum());
+        }
+
+        // the table stores the other side to move, so take the best DTZ after each move
+        let mut min_dtz = i32::MAX;
+        for move_ in board.legal_moves() {
+            let zeroing = move_.is_capture() || move_.piece() == Piece::Pawn;
+            board.push(&move_);
+            // for zeroing moves the DTZ is counted from before the move
+            let dtz = if zeroing {
+                self.search(board, false).map(|(wdl, _)| -dtz_before_zeroing(wdl))
+            } else {
+                self.dtz(board).map(|dtz| -dtz)
+            };
+            let is_mate = dtz == Some(1) && is_checkmate(board);
+            board.pop();
+            let mut dtz = dtz?;
+            if is_mate {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == wdl.value().signum() {
+                min_dtz = dtz;
+            }
+        }
+        // no legal moves: mated
+        Some(if min_dtz == i32::MAX { -1 } else { min_dtz })
+    }
+
+    // The DTZ of each legal move, counted from the root position
+    pub fn root_dtz(&self, board: &mut Board) -> Option<Vec<(Move, i32)>> {
+        if !self.can_probe(board) {
+            return None;
+        }
+        let mut result = vec![];
+        for move_ in board.legal_moves() {
+            board.push(&move_);
+            let dtz = if board.half_move_clock() == 0 {
+                self.search(board, false).map(|(wdl, _)| dtz_before_zeroing(-wdl))
+            } else if board.is_draw_by_threefold_repetition() || board.is_draw_by_fifty_move_rule() {
+                Some(0)
+            } else {
+                self.dtz(board).map(|dtz| -dtz - dtz.signum())
+            };
+            let dtz = match dtz {
+                Some(2) if is_checkmate(board) => Some(1),
+                dtz => dtz,
+            };
+            board.pop();
+            result.push((move_, dtz?));
+        }
+        Some(result)
+    }
+
+    // The moves that keep the best result reachable within the fifty move rule, preferring
+    // the fastest progress when winning and the longest resistance when losing
+    pub fn root_moves(&self, board: &mut Board) -> Option<Vec<Move>> {
+        let half_move_clock = board.half_move_clock() as i32;
+        let ranked: Vec<(Move, i32)> = self
+            .root_dtz(board)?
+            .into_iter()
+            .map(|(move_, dtz)| {
+                let rank = if dtz > 0 && dtz + half_move_clock <= 100 {
+                    1000 - dtz
+                } else if dtz < 0 && -dtz + half_move_clock <= 100 {
+                    -1000 - dtz
+                } else {
+                    0
+                };
+                (move_, rank)
+            })
+            .collect();
+        let best_rank = ranked.iter().map(|&(_, rank)| rank).max()?;
+        Some(ranked.into_iter().filter(|&(_, rank)| rank == best_rank).map(|(move_, _)| move_).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use test_case::test_case;
+
+    // The test code has leaves for the values 0-4 and two pair symbols expanding to two and
+    // four zeros. Symbols 5 and 6 have 2 bit codes, 2-4 have 3 bit codes and 0-1 4 bit codes.
+    const BTREE: [(usize, usize); 7] = [(1, 0xfff), (3, 0xfff), (2, 0xfff), (4, 0xfff), (0, 0xfff), (4, 4), (5, 5)];
+    const LEAF_SYMBOLS: [usize; 5] = [4, 0, 2, 1, 3];
+    const LOWEST_SYMBOLS: [usize; 3] = [5, 2, 0];
+    const BASES: [u64; 3] = [2, 1, 0];
+
+    struct Compressed {
+        header: Vec<u8>,
+        sparse_index: Vec<u8>,
+        block_lengths: Vec<u8>,
+        blocks: Vec<u8>,
+    }
+
+    fn compress(values: &[u8], log2_block_size: u8, log2_span: u8) -> Compressed {
+        let mut symbols = vec![];
+        let mut i = 0;
+        while i < values.len() {
+            let zeros = values[i..].iter().take(4).take_while(|&&v| v == 0).count();
+            let (sym, count) = match zeros {
+                4 => (6, 4),
+                2 | 3 => (5, 2),
+                _ => (LEAF_SYMBOLS[values[i] as usize], 1),
+            };
+            symbols.push((sym, count));
+            i += count;
+        }
+
+        let block_size = 1usize << log2_block_size;
+        let mut blocks: Vec<Vec<u8>> = vec![];
+        let mut block_starts = vec![];
+        let mut block_counts = vec![];
+        let mut bits = 0;
+        let mut start = 0;
+        for (sym, count) in symbols {
+            let len = LOWEST_SYMBOLS.iter().position(|&lowest| sym >= lowest).unwrap();
+            let code = BASES[len] + (sym - LOWEST_SYMBOLS[len]) as u64;
+            let len = len + 2;
+            if blocks.is_empty() || bits + len > 8 * block_size {
+                blocks.push(vec![0; block_size]);
+                block_starts.push(start);
+                block_counts.push(0);
+                bits = 0;
+            }
+            let block = blocks.last_mut().unwrap();
+            for b in 0..len {
+                if code >> (len - 1 - b) & 1 != 0 {
+                    block[(bits + b) / 8] |= 0x80 >> ((bits + b) % 8);
+                }
+            }
+            bits += len;
+            *block_counts.last_mut().unwrap() += count;
+            start += count;
+        }
+
+        let mut header = vec![0, log2_block_size, log2_span, 0];
+        header.extend((blocks.len() as u32).to_le_bytes());
+        header.extend([4, 2]);
+        for lowest in LOWEST_SYMBOLS {
+            header.extend((lowest as u16).to_le_bytes());
+        }
+        header.extend((BTREE.len() as u16).to_le_bytes());
+        for (left, right) in BTREE {
+            header.extend([left as u8, (left >> 8) as u8 | (right << 4) as u8, (right >> 4) as u8]);
+        }
+        header.push(0);
+
+        let span = 1usize << log2_span;
+        let mut sparse_index = vec![];
+        for k in 0..values.len().div_ceil(span) {
+            let target = k * span + span / 2;
+            let block = block_starts.iter().rposition(|&start| start <= target).unwrap();
+            sparse_index.extend((block as u32).to_le_bytes());
+            sparse_index.extend(((target - block_starts[block]) as u16).to_le_bytes());
+        }
+        let block_lengths = block_counts.iter().flat_map(|&count| (count as u16 - 1).to_le_bytes()).collect();
+        Compressed { header, sparse_index, block_lengths, blocks: blocks.concat() }
+    }
+
+    fn pad_to(data: &mut Vec<u8>, alignment: usize) {
+        data.resize(data.len().div_ceil(alignment) * alignment, 0);
+    }
+
+    fn kqvk_info() -> TableInfo {
+        TableInfo::new("KQvK").unwrap()
+    }
+
+    fn kqvk_pairs_data() -> PairsData {
+        let mut d = PairsData { pieces: [6, 5, 14, 0, 0, 0, 0], ..Default::default() };
+        d.set_groups(&kqvk_info(), [0, 0xf], 0);
+        d
+    }
+
+    fn kqvk_board(white_king: usize, white_queen: usize, black_king: usize, side: char) -> Option<Board> {
+        if white_king == white_queen || white_king == black_king || white_queen == black_king {
+            return None;
+        }
+        let placements = [("K", white_king), ("Q", white_queen), ("k", black_king)];
+        let fen = (0..8)
+            .rev()
+            .map(|rank| {
+                let mut row = String::new();
+                let mut empty = 0;
+                for file in 0..8 {
+                    match placements.iter().find(|&&(_, square)| square == rank * 8 + file) {
+                        Some((piece, _)) => {
+                            if empty > 0 {
+                                row.push_str(&empty.to_string());
+                                empty = 0;
+                            }
+                            row.push_str(piece);
+                        }
+                        None => empty += 1,
+                    }
+                }
+                if empty > 0 {
+                    row.push_str(&empty.to_string());
+                }
+                row
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        let board = Board::parse_fen(&format!("{} {} - - 0 1", fen, side)).ok()?;
+        board.validate().ok()?;
+        Some(board)
+    }
+
+    // A WDL table for KQvK: white to move always wins, black to move loses unless stalemated
+    // or able to take the queen. Only positions with the white king in the a1-d1-d4 triangle
+    // are generated, the encoding maps every other position onto them.
+    fn write_kqvk_table(dir: &std::path::Path) {
+        let d = kqvk_pairs_data();
+        let mut values = vec![2u8; d.group_idx[1] as usize];
+        for white_king in [0, 1, 2, 3, 9, 10, 11, 18, 19, 27] {
+            for white_queen in 0..64 {
+                for black_king in 0..64 {
+                    let board = match kqvk_board(white_king, white_queen, black_king, 'b') {
+                        Some(board) => board,
+                        None => continue,
+                    };
+                    let moves = board.legal_moves();
+                    let value = if moves.is_empty() {
+                        if board.is_in_check() { 0 } else { 2 }
+                    } else if moves.iter().any(|m| m.is_capture()) {
+                        2
+                    } else {
+                        0
+                    };
+                    let items = vec![vec![d.clone()], vec![d.clone()]];
+                    let (side, _, idx) = encode(&board, &kqvk_info(), &items, false, true).unwrap();
+                    assert_eq!(side, 1);
+                    values[idx as usize] = value;
+                }
+            }
+        }
+
+        let compressed = compress(&values, 6, 5);
+        let mut data = WDL_MAGIC.to_vec();
+        data.extend([SPLIT, 0x00, 0x66, 0x55, 0xee]);
+        pad_to(&mut data, 2);
+        data.extend([FLAG_SINGLE_VALUE, 4]);
+        data.extend(&compressed.header);
+        data.extend(&compressed.sparse_index);
+        data.extend(&compressed.block_lengths);
+        pad_to(&mut data, 64);
+        data.extend(&compressed.blocks);
+        std::fs::write(dir.join("KQvK.rtbw"), data).unwrap();
+    }
+
+    lazy_static! {
+        static ref KQVK_DIR: std::path::PathBuf = {
+            let dir = std::env::temp_dir().join(format!("chessica-syzygy-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            write_kqvk_table(&dir);
+            dir
+        };
+    }
+
+    #[test]
+    fn test_encoding_tables() {
+        let encoding = &*ENCODING;
+        let codes: HashSet<u64> = (0..10)
+            .flat_map(|idx| (0..64).map(move |square| (idx, square)))
+            .filter(|&(idx, square)| encoding.map_kk[idx][square] != 0)
+            .map(|(idx, square)| encoding.map_kk[idx][square])
+            .collect();
+        assert_eq!(codes.len(), 461);
+        assert_eq!(codes.iter().max(), Some(&461));
+        assert_eq!(encoding.binomial[3][10], 120);
+        assert_eq!(encoding.map_pawns.iter().max(), Some(&47));
+        assert_eq!(encoding.lead_pawns_size[1], [6, 6, 6, 6]);
+    }
+
+    #[test]
+    fn test_wdl_negation() {
+        assert_eq!(-Wdl::Win, Wdl::Loss);
+        assert_eq!(-Wdl::CursedWin, Wdl::BlessedLoss);
+        assert_eq!(-Wdl::Draw, Wdl::Draw);
+        assert!(Wdl::Loss < Wdl::BlessedLoss && Wdl::CursedWin < Wdl::Win);
+    }
+
+    #[test_case("1k6/8/1K6/8/8/8/8/Q7 w - - 0 1", "6k1/8/6K1/8/8/8/8/7Q w - - 0 1"; "files")]
+    #[test_case("1k6/8/1K6/8/8/8/8/Q7 w - - 0 1", "Q7/8/8/8/8/1K6/8/1k6 w - - 0 1"; "ranks")]
+    #[test_case("8/8/2k5/8/3Q4/8/1K6/8 b - - 0 1", "8/8/8/8/3Q4/5k2/1K6/8 b - - 0 1"; "diagonal")]
+    #[test_case("8/8/2k5/8/3Q4/8/1K6/8 b - - 0 1", "8/1k6/8/3q4/8/2K5/8/8 w - - 0 1"; "colors")]
+    fn test_encode_symmetry(fen: &str, other_fen: &str) {
+        let items = vec![vec![kqvk_pairs_data()], vec![kqvk_pairs_data()]];
+        let encode_fen = |fen: &str, flip: bool| {
+            let board = Board::parse_fen(fen).unwrap();
+            encode(&board, &kqvk_info(), &items, flip, true).unwrap()
+        };
+        let other_flipped = other_fen.contains('q');
+        let (side, file, idx) = encode_fen(fen, false);
+        assert_eq!(encode_fen(other_fen, other_flipped), (side, file, idx));
+        assert!(idx < kqvk_pairs_data().group_idx[1]);
+    }
+
+    #[test_case(6, 4; "small blocks")]
+    #[test_case(8, 7; "large blocks")]
+    fn test_decompress(log2_block_size: u8, log2_span: u8) {
+        let mut seed = 12345u32;
+        let values: Vec<u8> = (0..5000)
+            .map(|i| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                if i % 700 < 300 { 0 } else { (seed >> 16) as u8 % 5 }
+            })
+            .collect();
+        let compressed = compress(&values, log2_block_size, log2_span);
+        let mut data = compressed.header.clone();
+        let sparse_index = data.len();
+        data.extend(&compressed.sparse_index);
+        let block_length = data.len();
+        data.extend(&compressed.block_lengths);
+        pad_to(&mut data, 64);
+        let blocks = data.len();
+        data.extend(&compressed.blocks);
+
+        let mut d = PairsData::default();
+        d.group_len[0] = 1;
+        d.group_idx[1] = values.len() as u64;
+        assert_eq!(d.set_sizes(&data, 0), Some(compressed.header.len()));
+        d.sparse_index = sparse_index;
+        d.block_length = block_length;
+        d.data = blocks;
+        for (idx, &value) in values.iter().enumerate() {
+            assert_eq!(d.decompress(&data, idx as u64), Some(value as i32), "index {}", idx);
+        }
+    }
+
+    #[test_case("8/8/8/4k3/8/8/8/1QK5 w - - 0 1", Some(Wdl::Win))]
+    #[test_case("8/8/8/4k3/8/8/8/1QK5 b - - 0 1", Some(Wdl::Loss))]
+    #[test_case("k1Q5/8/1K6/8/8/8/8/8 b - - 0 1", Some(Wdl::Loss); "mated")]
+    #[test_case("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1", Some(Wdl::Draw); "stalemate")]
+    #[test_case("8/8/8/8/8/8/1kQ5/7K b - - 0 1", Some(Wdl::Draw); "queen capture")]
+    #[test_case("8/8/8/8/8/8/1kQ5/3K4 b - - 0 1", Some(Wdl::Loss); "defended queen")]
+    #[test_case("kq6/8/8/8/8/8/8/7K b - - 0 1", Some(Wdl::Win); "black queen")]
+    #[test_case("kq6/8/8/8/8/8/8/7K w - - 0 1", Some(Wdl::Loss); "black queen white to move")]
+    #[test_case("8/8/8/4k3/8/8/8/K7 w - - 0 1", Some(Wdl::Draw); "bare kings")]
+    #[test_case("8/8/8/4k3/8/8/8/1Q2K2R w K - 0 1", None; "castling rights")]
+    #[test_case("8/8/8/4k3/8/8/8/1QR1K3 w - - 0 1", None; "too many pieces")]
+    #[test_case("8/8/8/4k3/8/8/8/1RK5 w - - 0 1", None; "missing table")]
+    fn test_probe_wdl(fen: &str, expected: Option<Wdl>) {
+        let tablebase = Tablebase::new(KQVK_DIR.to_str().unwrap());
+        assert_eq!(tablebase.max_pieces(), 3);
+        let mut board = Board::parse_fen(fen).unwrap();
+        assert_eq!(tablebase.probe_wdl(&mut board), expected);
+        assert_eq!(board.to_fen_string(), fen);
+        // draws need no DTZ table
+        let expected_dtz = if expected == Some(Wdl::Draw) { Some(0) } else { None };
+        assert_eq!(tablebase.probe_dtz(&mut board), expected_dtz);
+    }
+
+    // Probes the real tables in tests/data/syzygy, or in the directories in CHESSICA_SYZYGY_PATH
+    #[test_case("8/8/8/8/8/8/2Rk4/1K6 b - - 0 1", Wdl::Draw, 0; "rook capture")]
+    #[test_case("8/8/8/4k3/8/8/8/1QK5 w - - 0 1", Wdl::Win, 15)]
+    #[test_case("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", Wdl::Win, 1; "pawn move")]
+    #[test_case("8/8/8/8/8/4k3/4p3/2K5 w - - 0 1", Wdl::Draw, 0; "drawn pawn")]
+    #[test_case("8/4k3/8/8/8/8/3KBN2/8 b - - 0 1", Wdl::Loss, -54)]
+    #[ignore = "needs the fixture tables listed in tests/data/syzygy/README.md"]
+    fn test_real_tables(fen: &str, wdl: Wdl, dtz: i32) {
+        let path = std::env::var("CHESSICA_SYZYGY_PATH")
+            .unwrap_or_else(|_| concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/syzygy").to_string());
+        let tablebase = Tablebase::new(&path);
+        let mut board = Board::parse_fen(fen).unwrap();
+        assert_eq!(tablebase.probe_wdl(&mut board), Some(wdl));
+        assert_eq!(tablebase.probe_dtz(&mut board), Some(dtz));
+    }
+}